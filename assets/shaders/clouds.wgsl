@@ -0,0 +1,22 @@
+#import bevy_pbr::mesh_vertex_output MeshVertexOutput
+#import bevy_pbr::mesh_view_bindings view
+
+struct CloudMaterial {
+    color: vec4<f32>,
+    fade_start: f32,
+    fade_end: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> material: CloudMaterial;
+
+@fragment
+fn fragment(in: MeshVertexOutput) -> @location(0) vec4<f32> {
+    // shaded by face like blocks in the sun: tops are brightest, bottoms darkest
+    let normal = normalize(in.world_normal);
+    let shade = select(select(0.8, 1.0, normal.y > 0.5), 0.65, normal.y < -0.5);
+
+    let distance = length(in.world_position.xz - view.world_position.xz);
+    let fade = 1.0 - smoothstep(material.fade_start, material.fade_end, distance);
+    return vec4<f32>(material.color.rgb * shade, material.color.a * fade);
+}
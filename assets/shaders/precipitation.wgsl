@@ -0,0 +1,93 @@
+#import bevy_pbr::mesh_view_bindings view
+
+struct PrecipitationMaterial {
+    time: f32,
+    intensity: f32,
+    snow: f32,
+    brightness: f32,
+    heightmap_origin: vec2<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> material: PrecipitationMaterial;
+@group(1) @binding(1)
+var heightmap: texture_2d<f32>;
+
+const PARTICLE_COUNT: f32 = 6000.0;
+// size of the area around the camera particles fall in
+const AREA: vec3<f32> = vec3<f32>(48.0, 32.0, 48.0);
+
+struct Vertex {
+    @builtin(vertex_index) index: u32,
+    @location(0) seed: vec3<f32>,
+    @location(2) corner: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) fade: f32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.corner = vertex.corner;
+    out.fade = 0.0;
+    // hidden particles collapse outside of the view
+    out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+
+    let particle = f32(vertex.index / 4u);
+    if particle >= material.intensity * PARTICLE_COUNT {
+        return out;
+    }
+
+    let speed = mix(9.0, 1.5, material.snow) * (0.8 + 0.4 * vertex.seed.x);
+    var position = vertex.seed * AREA;
+    position.y -= material.time * speed;
+    // snowflakes drift sideways
+    position.x += material.snow * sin(material.time * 0.7 + vertex.seed.z * 40.0);
+    position.z += material.snow * cos(material.time * 0.6 + vertex.seed.x * 40.0);
+
+    // wrap into the area around the camera, particles stay in place while the camera moves
+    let area_min = view.world_position - AREA * 0.5;
+    let offset = position - area_min;
+    position = area_min + offset - floor(offset / AREA) * AREA;
+
+    // blocks overhead stop precipitation
+    let texel = vec2<i32>(floor(position.xz - material.heightmap_origin));
+    let size = vec2<i32>(textureDimensions(heightmap));
+    if all(texel >= vec2<i32>(0)) && all(texel < size) && position.y < textureLoad(heightmap, texel, 0).r {
+        return out;
+    }
+
+    // raindrops turn only around the vertical axis, snowflakes face the camera
+    let to_camera = view.world_position - position;
+    var right = cross(vec3<f32>(0.0, 1.0, 0.0), to_camera);
+    if length(right) < 0.001 {
+        right = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    right = normalize(right);
+    let up = normalize(mix(vec3<f32>(0.0, 1.0, 0.0), normalize(cross(to_camera, right)), material.snow));
+    let quad_size = mix(vec2<f32>(0.03, 0.7), vec2<f32>(0.1, 0.1), material.snow);
+    let world_position = position
+        + right * (vertex.corner.x - 0.5) * quad_size.x
+        + up * (vertex.corner.y - 0.5) * quad_size.y;
+
+    out.clip_position = view.view_proj * vec4<f32>(world_position, 1.0);
+    out.fade = 1.0 - smoothstep(AREA.x * 0.3, AREA.x * 0.5, length(to_camera.xz));
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // raindrops are streaks fading towards their ends, snowflakes are round
+    let from_center = in.corner - 0.5;
+    let streak = 1.0 - abs(from_center.y) * 2.0;
+    let flake = 1.0 - smoothstep(0.3, 0.5, length(from_center));
+    let shape = mix(streak, flake, material.snow);
+
+    let color = mix(vec3<f32>(0.55, 0.6, 0.75), vec3<f32>(1.0), material.snow) * material.brightness;
+    let alpha = shape * in.fade * mix(0.35, 0.9, material.snow);
+    return vec4<f32>(color, alpha);
+}
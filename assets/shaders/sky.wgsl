@@ -0,0 +1,53 @@
+#import bevy_pbr::mesh_vertex_output MeshVertexOutput
+#import bevy_pbr::mesh_view_bindings view
+
+struct SkyMaterial {
+    sun_direction: vec3<f32>,
+    night_blend: f32,
+    moon_direction: vec3<f32>,
+    twilight: f32,
+    overcast: f32,
+    fog_color: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> sky: SkyMaterial;
+@group(1) @binding(1)
+var day_texture: texture_cube<f32>;
+@group(1) @binding(2)
+var day_sampler: sampler;
+@group(1) @binding(3)
+var night_texture: texture_cube<f32>;
+@group(1) @binding(4)
+var night_sampler: sampler;
+
+@fragment
+fn fragment(in: MeshVertexOutput) -> @location(0) vec4<f32> {
+    let direction = normalize(in.world_position.xyz - view.world_position);
+    // cubemaps are left handed
+    let sample_direction = direction * vec3<f32>(1.0, 1.0, -1.0);
+
+    let day = textureSample(day_texture, day_sampler, sample_direction).rgb;
+    let night = textureSample(night_texture, night_sampler, sample_direction).rgb;
+    var color = mix(day, night, sky.night_blend);
+
+    // warm glow on the side of the sky the sun rises or sets
+    let sun_dot = dot(direction, sky.sun_direction);
+    let glow = sky.twilight * smoothstep(-0.3, 1.0, sun_dot) * (1.0 - abs(direction.y));
+    color = mix(color, vec3<f32>(1.0, 0.45, 0.2), glow * 0.6);
+
+    let sun_disc = smoothstep(0.9990, 0.9994, sun_dot);
+    color += vec3<f32>(20.0, 17.0, 12.0) * sun_disc;
+    let moon_disc = smoothstep(0.9994, 0.9996, dot(direction, sky.moon_direction));
+    color += vec3<f32>(1.6, 1.7, 2.0) * moon_disc;
+
+    // clouds hide the sun and the moon and turn the sky grey
+    let grey = dot(color, vec3<f32>(0.3, 0.59, 0.11)) * 0.5;
+    color = mix(color, vec3<f32>(min(grey, 0.5)), sky.overcast);
+
+    // the horizon matches the distance fog over the terrain
+    let horizon = 1.0 - smoothstep(-0.05, 0.3, direction.y);
+    color = mix(color, sky.fog_color.rgb, max(horizon, sky.fog_color.a));
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,75 @@
+#import bevy_pbr::mesh_vertex_output MeshVertexOutput
+#import bevy_pbr::mesh_view_bindings view, globals, fog
+#import bevy_pbr::fog apply_fog
+#import bevy_pbr::prepass_utils
+
+struct WaterMaterial {
+    shallow_color: vec4<f32>,
+    deep_color: vec4<f32>,
+    sky_brightness: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> material: WaterMaterial;
+@group(1) @binding(1)
+var sky_texture: texture_cube<f32>;
+@group(1) @binding(2)
+var sky_sampler: sampler;
+
+// normal of a few moving sine waves, their slopes tilt the flat surface
+fn wave_normal(position: vec2<f32>, time: f32) -> vec3<f32> {
+    // direction, frequency and speed of each wave
+    var waves = array<vec4<f32>, 4>(
+        vec4<f32>(1.0, 0.3, 1.3, 1.1),
+        vec4<f32>(-0.4, 1.0, 2.1, 1.6),
+        vec4<f32>(0.7, -0.8, 3.7, 2.3),
+        vec4<f32>(-0.9, -0.2, 5.3, 3.1),
+    );
+    var slope = vec2<f32>(0.0);
+    for (var i = 0; i < 4; i += 1) {
+        let wave = waves[i];
+        let direction = normalize(wave.xy);
+        let phase = dot(direction, position) * wave.z + time * wave.w;
+        slope += direction * cos(phase) * 0.05;
+    }
+    return normalize(vec3<f32>(-slope.x, 1.0, -slope.y));
+}
+
+@fragment
+fn fragment(in: MeshVertexOutput) -> @location(0) vec4<f32> {
+    let to_camera = normalize(view.world_position - in.world_position.xyz);
+    let face_normal = normalize(in.world_normal);
+    var normal = face_normal;
+    if face_normal.y > 0.5 {
+        normal = wave_normal(in.world_position.xz, globals.time);
+    }
+
+    // water seen from below doesn't reflect the sky
+    var fresnel = 0.0;
+    if dot(face_normal, to_camera) > 0.0 {
+        fresnel = 0.02 + 0.98 * pow(1.0 - max(dot(normal, to_camera), 0.0), 5.0);
+    }
+    // cubemaps are left handed
+    let reflected = reflect(-to_camera, normal) * vec3<f32>(1.0, 1.0, -1.0);
+    let sky = textureSample(sky_texture, sky_sampler, reflected).rgb * material.sky_brightness;
+
+    // the ground below is in the depth prepass, water isn't
+    var thickness = 4.0;
+#ifdef DEPTH_PREPASS
+    let near = view.projection[3][2];
+    let ground_depth = near / bevy_pbr::prepass_utils::prepass_depth(in.position, 0u);
+    let water_depth = near / in.position.z;
+    thickness = max(ground_depth - water_depth, 0.0);
+#endif
+    let body = mix(material.shallow_color, material.deep_color, 1.0 - exp(-thickness * 0.3));
+
+    // vertex colors carry the block light baked into the chunk mesh
+    var light = 1.0;
+#ifdef VERTEX_COLORS
+    light = in.color.g;
+#endif
+
+    let color = mix(body.rgb * light, sky, fresnel);
+    let alpha = mix(body.a, 1.0, fresnel);
+    return apply_fog(fog, vec4<f32>(color, alpha), in.world_position.xyz, view.world_position);
+}
@@ -0,0 +1,112 @@
+//! Collider rebuild of a dug chunk: the merged cuboid compound against the triangle mesh
+//! of visible faces it replaced. Run with `cargo bench --bench chunk_collider`.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use block_mesh::ndshape::{ConstShape, ConstShape3u32};
+use block_mesh::{visible_block_faces, UnitQuadBuffer, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use minecraft_bevy_rust::blocks::{BlockId, BlockList, BlockRegistry};
+use minecraft_bevy_rust::collision::chunk_collider;
+use minecraft_bevy_rust::models::BlockModel;
+
+/// A padded chunk like the ones of the terrain.
+type SampleShape = ConstShape3u32<34, 34, 34>;
+
+struct CollisionVoxel(VoxelVisibility);
+
+impl Voxel for CollisionVoxel {
+    fn get_visibility(&self) -> VoxelVisibility {
+        self.0
+    }
+}
+
+fn registry() -> BlockRegistry {
+    let list: BlockList = serde_json::from_str(include_str!("../assets/default.blocks.json")).unwrap();
+    BlockRegistry::new(list)
+}
+
+/// Rolling hills of stone under dirt and grass with a few fences and slabs on top,
+/// and one voxel dug out of the middle like a player would.
+fn dug_chunk(registry: &BlockRegistry) -> Vec<BlockId> {
+    let [stone, dirt, grass] = ["stone", "dirt", "grass"].map(|name| registry.id(name));
+    let [fence, slab] = ["oak_fence", "cobblestone_slab"].map(|name| registry.id(name));
+    let mut blocks = vec![BlockId::default(); SampleShape::SIZE as usize];
+    for index in 0..SampleShape::SIZE {
+        let [x, y, z] = SampleShape::delinearize(index);
+        let height = 16 + ((x as f32 * 0.3).sin() * 4.0 + (z as f32 * 0.2).cos() * 3.0) as u32;
+        blocks[index as usize] = match y {
+            y if y + 3 < height => stone,
+            y if y < height => dirt,
+            y if y == height => grass,
+            y if y == height + 1 && (x + z) % 7 == 0 => fence,
+            y if y == height + 1 && (x * z) % 11 == 0 => slab,
+            _ => continue,
+        };
+    }
+    blocks[SampleShape::linearize([17, 12, 17]) as usize] = BlockId::default();
+    blocks
+}
+
+fn compound_collider(blocks: &[BlockId], registry: &BlockRegistry) -> Option<Collider> {
+    chunk_collider(|index| blocks[index as usize], &SampleShape {}, registry)
+}
+
+/// The previous collider: visible faces of solid cubes and every face of model boxes as triangles.
+fn trimesh_collider(blocks: &[BlockId], registry: &BlockRegistry) -> Option<Collider> {
+    let voxels: Vec<CollisionVoxel> = blocks.iter()
+        .map(|block| {
+            let block = registry.get(*block);
+            let solid = block.collision && block.model == BlockModel::Cube;
+            CollisionVoxel(if solid { VoxelVisibility::Opaque } else { VoxelVisibility::Empty })
+        })
+        .collect();
+    let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
+    let mut buffer = UnitQuadBuffer::new();
+    visible_block_faces(&voxels, &SampleShape {}, [0; 3], [33; 3], &faces, &mut buffer);
+
+    let mut vertices: Vec<Vec3> = Vec::new();
+    let mut indices: Vec<[u32; 3]> = Vec::new();
+    for (group, face) in buffer.groups.into_iter().zip(faces.into_iter()) {
+        for quad in group.into_iter() {
+            let start = vertices.len() as u32;
+            let [a, b, c, d, e, f] = face.quad_mesh_indices(start);
+            indices.extend_from_slice(&[[a, b, c], [d, e, f]]);
+            vertices.extend(face.quad_mesh_positions(&quad.into(), 1.0).map(Vec3::from));
+        }
+    }
+
+    for index in 0..SampleShape::SIZE {
+        let block = registry.get(blocks[index as usize]);
+        if !block.collision || block.model == BlockModel::Cube {
+            continue;
+        }
+        let origin = Vec3::from(SampleShape::delinearize(index).map(|coordinate| coordinate as f32));
+        for (min, max) in block.model.collision_boxes() {
+            let start = vertices.len() as u32;
+            vertices.extend((0..8).map(|corner| origin + Vec3::select(
+                BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0), *max, *min)));
+            indices.extend(BOX_TRIANGLES.map(|triangle| triangle.map(|corner| start + corner)));
+        }
+    }
+
+    (!indices.is_empty()).then(|| Collider::trimesh(vertices, indices))
+}
+
+/// Triangles of a box whose corners are numbered by their x, y and z bits.
+const BOX_TRIANGLES: [[u32; 3]; 12] = [
+    [0, 2, 1], [1, 2, 3], [4, 5, 6], [5, 7, 6],
+    [0, 1, 4], [1, 5, 4], [2, 6, 3], [3, 6, 7],
+    [0, 4, 2], [2, 4, 6], [1, 3, 5], [3, 7, 5],
+];
+
+fn collider_after_dig(c: &mut Criterion) {
+    let registry = registry();
+    let blocks = dug_chunk(&registry);
+    c.bench_function("compound_after_dig", |b| b.iter(|| black_box(compound_collider(&blocks, &registry))));
+    c.bench_function("trimesh_after_dig", |b| b.iter(|| black_box(trimesh_collider(&blocks, &registry))));
+}
+
+criterion_group!(benches, collider_after_dig);
+criterion_main!(benches);
@@ -0,0 +1,347 @@
+//! Block texture atlas packed at startup from the PNGs in `assets/textures/blocks/`.
+//!
+//! Resource packs are folders in `assets/resourcepacks/`, their `textures/blocks/` files
+//! replace the default textures with the same file name. Packs are applied in name order.
+//!
+//! A texture is animated when a `<texture>.anim.json` file sits next to it. The texture is then
+//! a vertical strip of square frames, the current frame is copied into the atlas so chunk meshes
+//! keep their UVs. Copying uploads the whole atlas again, so animations only advance once per
+//! tick like in Minecraft, interpolated ones too.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use bevy::asset::{HandleId, LoadState};
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::sprite::TextureAtlasBuilder;
+
+use crate::blocks::BlockRegistry;
+
+const BLOCK_TEXTURES_FOLDER: &str = "textures/blocks";
+const RESOURCE_PACKS_FOLDER: &str = "resourcepacks";
+/// Drawn instead of textures missing from the atlas.
+const FALLBACK_TEXTURE: &str = "debug.png";
+const ANIMATION_EXTENSION: &str = ".anim.json";
+/// Seconds between two updates of the animated textures.
+const ANIMATION_TICK: f32 = 1.0 / 20.0;
+
+/// Contents of a `<texture>.anim.json` file.
+#[derive(serde::Deserialize, bevy::reflect::TypeUuid, bevy::reflect::TypePath)]
+#[uuid = "5f0c2a8e-7d3b-4e61-9a4f-2b8c6d1e0f37"]
+pub struct TextureAnimation {
+    /// Seconds each frame is shown.
+    frame_time: f32,
+    /// Frames in the strip, all square frames of the texture by default.
+    #[serde(default)]
+    frame_count: Option<u32>,
+    /// Blend into the next frame instead of switching at once.
+    #[serde(default)]
+    interpolate: bool,
+}
+
+/// Animation strip of a texture and the atlas region it is copied to.
+struct AnimatedTexture {
+    region: URect,
+    /// RGBA8 pixels of the whole strip.
+    strip: Vec<u8>,
+    frame_count: u32,
+    frame_time: f32,
+    interpolate: bool,
+    /// Frame currently in the atlas, interpolated animations change every tick.
+    shown_frame: Option<u32>,
+}
+
+impl AnimatedTexture {
+    fn frame_pixels(&self, frame: u32) -> &[u8] {
+        let frame_len = (self.region.width() * self.region.height() * 4) as usize;
+        let start = frame as usize * frame_len;
+        &self.strip[start..start + frame_len]
+    }
+
+    /// Copies the frame at `seconds` into the atlas pixels, returns false if they are unchanged.
+    fn write(&mut self, seconds: f32, atlas: &mut [u8], atlas_width: u32) -> bool {
+        let position = seconds / self.frame_time;
+        let frame = position as u32 % self.frame_count;
+        if !self.interpolate && self.shown_frame == Some(frame) {
+            return false;
+        }
+        self.shown_frame = Some(frame);
+
+        let current = self.frame_pixels(frame);
+        let next = self.frame_pixels((frame + 1) % self.frame_count);
+        let blend = if self.interpolate { position.fract() } else { 0.0 };
+
+        let row_len = (self.region.width() * 4) as usize;
+        for row in 0..self.region.height() {
+            let source = row as usize * row_len;
+            let target = (((self.region.min.y + row) * atlas_width + self.region.min.x) * 4) as usize;
+            for i in 0..row_len {
+                let from = current[source + i] as f32;
+                let to = next[source + i] as f32;
+                atlas[target + i] = (from + (to - from) * blend).round() as u8;
+            }
+        }
+        true
+    }
+}
+
+/// Block icons built from the atlas once the blocks are loaded, see [`BlockAtlas::icons`].
+#[derive(Resource)]
+pub struct BlockIcons(pub Handle<TextureAtlas>);
+
+/// Packed block textures, frames are looked up by texture file name, e.g. `stone.png`.
+pub struct BlockAtlas {
+    pub image: Handle<Image>,
+    size: Vec2,
+    frames: HashMap<String, Rect>,
+    /// Missing names already warned about, meshing runs every frame.
+    missing: Mutex<HashSet<String>>,
+    animations: Vec<AnimatedTexture>,
+    /// Animation tick currently in the atlas.
+    shown_tick: Option<u32>,
+}
+
+impl BlockAtlas {
+    fn frame(&self, name: &str) -> Rect {
+        if let Some(frame) = self.frames.get(name) {
+            return *frame;
+        }
+        if self.missing.lock().unwrap().insert(name.to_string()) {
+            warn!("Missing block texture {name}, using {FALLBACK_TEXTURE}");
+        }
+        *self.frames.get(FALLBACK_TEXTURE)
+            .unwrap_or_else(|| panic!("Missing fallback block texture {FALLBACK_TEXTURE}"))
+    }
+
+    /// Warns about every block face whose texture isn't in the atlas.
+    pub fn report_missing_textures(&self, registry: &BlockRegistry) {
+        for block in registry.iter().filter(|block| block.is_visible()) {
+            for (face, normal) in [("top", Vec3::Y), ("bottom", Vec3::NEG_Y), ("side", Vec3::X)] {
+                let name = block.texture(normal);
+                if !self.frames.contains_key(name) {
+                    warn!("Block {} has no texture {name} for its {face} face", block.name);
+                }
+            }
+        }
+    }
+
+    /// Updates the atlas regions of animated textures.
+    pub fn animate(&mut self, seconds: f32, images: &mut Assets<Image>) {
+        let tick = (seconds / ANIMATION_TICK) as u32;
+        if self.animations.is_empty() || self.shown_tick == Some(tick) {
+            return;
+        }
+        self.shown_tick = Some(tick);
+        let seconds = tick as f32 * ANIMATION_TICK;
+
+        // get_mut reuploads the whole atlas, only take it when a region changes
+        let changed = self.animations.iter()
+            .any(|animation| animation.interpolate
+                || animation.shown_frame != Some((seconds / animation.frame_time) as u32 % animation.frame_count));
+        if !changed {
+            return;
+        }
+        if let Some(image) = images.get_mut(&self.image) {
+            let width = image.texture_descriptor.size.width;
+            for animation in &mut self.animations {
+                animation.write(seconds, &mut image.data, width);
+            }
+        }
+    }
+
+    /// Atlas of block icons for the UI, indexed by block id, showing the side of each block.
+    pub fn icons(&self, registry: &BlockRegistry) -> TextureAtlas {
+        let mut icons = TextureAtlas::new_empty(self.image.clone(), self.size);
+        for block in registry.iter() {
+            icons.add_texture(self.frame(block.texture(Vec3::X)));
+        }
+        icons
+    }
+
+    /// UVs of a whole frame as bottom left, bottom right, top left, top right.
+    pub fn uv(&self, name: &str) -> [[f32; 2]; 4] {
+        self.sub_uv(name, [Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0), Vec2::new(1.0, 1.0)])
+    }
+
+    /// UVs of a part of a frame, `tex_coords` go from the bottom left corner of the frame.
+    pub fn sub_uv(&self, name: &str, tex_coords: [Vec2; 4]) -> [[f32; 2]; 4] {
+        let frame = self.frame(name);
+        tex_coords.map(|coords| [
+            (frame.min.x + coords.x * frame.width()) / self.size.x,
+            (frame.min.y + (1.0 - coords.y) * frame.height()) / self.size.y,
+        ])
+    }
+}
+
+#[cfg(test)]
+impl BlockAtlas {
+    /// An atlas holding only the fallback texture, every block is drawn with it.
+    pub fn fallback_only() -> Self {
+        let frames = HashMap::from([(FALLBACK_TEXTURE.to_string(), Rect::new(0.0, 0.0, 16.0, 16.0))]);
+        Self {
+            image: Handle::default(),
+            size: Vec2::splat(16.0),
+            frames,
+            missing: Mutex::default(),
+            animations: Vec::new(),
+            shown_tick: None,
+        }
+    }
+}
+
+/// Block texture files being loaded, later folders override earlier ones.
+#[derive(Default)]
+pub struct BlockTextureLoading {
+    folders: Vec<Vec<HandleUntyped>>,
+}
+
+impl BlockTextureLoading {
+    pub fn start(asset_server: &AssetServer) -> Self {
+        let mut folders = vec![PathBuf::from(BLOCK_TEXTURES_FOLDER)];
+        folders.extend(resource_pack_folders(asset_server));
+
+        let folders = folders.into_iter()
+            .filter_map(|folder| match asset_server.load_folder(&folder) {
+                Ok(handles) => Some(handles),
+                Err(err) => {
+                    warn!("Can't load block textures from {}: {err:?}", folder.display());
+                    None
+                }
+            })
+            .collect();
+        Self { folders }
+    }
+
+    /// Whether every file finished loading, files that failed count as missing.
+    pub fn is_loaded(&self, asset_server: &AssetServer) -> bool {
+        self.folders.iter().flatten()
+            .all(|handle| matches!(asset_server.get_load_state(handle.id()), LoadState::Loaded | LoadState::Failed))
+    }
+
+    /// Packs every texture into one image, only the last file of each name is used.
+    /// Files that failed to load are skipped like missing ones, see [`FALLBACK_TEXTURE`].
+    /// Animated textures take the space of one frame.
+    pub fn build(
+        &self,
+        asset_server: &AssetServer,
+        images: &mut Assets<Image>,
+        animations: &Assets<TextureAnimation>,
+    ) -> BlockAtlas {
+        let now = SystemTime::now();
+
+        let mut textures: HashMap<String, Handle<Image>> = HashMap::new();
+        let mut animation_files: HashMap<String, Handle<TextureAnimation>> = HashMap::new();
+        for handle in self.folders.iter().flatten() {
+            if asset_server.get_load_state(handle.id()) == LoadState::Failed {
+                let path = asset_server.get_handle_path(handle.id())
+                    .map(|path| path.path().display().to_string())
+                    .unwrap_or_default();
+                warn!("Can't load block texture {path}, it is treated as missing");
+                continue;
+            }
+            match file_name(asset_server, handle.id()) {
+                Some(name) if name.ends_with(".png") => {
+                    textures.insert(name, handle.clone().typed());
+                }
+                Some(name) if name.ends_with(ANIMATION_EXTENSION) => {
+                    let texture = name.trim_end_matches(ANIMATION_EXTENSION).to_string();
+                    animation_files.insert(texture, handle.clone().typed());
+                }
+                _ => {}
+            }
+        }
+
+        // animated textures are packed as their first frame, the strips are kept for animating
+        let mut strips = HashMap::new();
+        for (name, handle) in &mut textures {
+            let Some(animation) = animation_files.get(name).and_then(|handle| animations.get(handle)) else {
+                continue;
+            };
+            let Some(image) = images.get(handle) else {
+                continue;
+            };
+            match animation_strip(name, image, animation) {
+                Some((first_frame, strip)) => {
+                    *handle = images.add(first_frame);
+                    strips.insert(name.clone(), strip);
+                }
+                None => warn!("Can't animate block texture {name}"),
+            }
+        }
+
+        let mut builder = TextureAtlasBuilder::default();
+        for handle in textures.values() {
+            if let Some(image) = images.get(handle) {
+                builder.add_texture(handle.clone(), image);
+            }
+        }
+        let atlas = builder.finish(images).expect("Block textures don't fit into the atlas");
+
+        let frames: HashMap<String, Rect> = textures.iter()
+            .filter_map(|(name, handle)| atlas.get_texture_index(handle).map(|index| (name.clone(), atlas.textures[index])))
+            .collect();
+
+        let animations = strips.into_iter()
+            .filter_map(|(name, mut strip)| {
+                images.remove(&textures[&name]);
+                let frame = frames.get(&name)?;
+                strip.region = URect::from_corners(frame.min.as_uvec2(), frame.max.as_uvec2());
+                Some(strip)
+            })
+            .collect();
+
+        println!("Atlas build time: {}ms", now.elapsed().unwrap().as_millis());
+        BlockAtlas { image: atlas.texture, size: atlas.size, frames, missing: Mutex::default(), animations, shown_tick: None }
+    }
+}
+
+/// `textures/blocks` folders of the installed resource packs in name order.
+fn resource_pack_folders(asset_server: &AssetServer) -> Vec<PathBuf> {
+    let asset_io = asset_server.asset_io();
+    let mut packs: Vec<PathBuf> = match asset_io.read_directory(Path::new(RESOURCE_PACKS_FOLDER)) {
+        Ok(entries) => entries.filter(|path| asset_io.is_dir(path)).collect(),
+        Err(_) => return Vec::new(),
+    };
+    packs.sort();
+    packs.into_iter()
+        .map(|pack| pack.join(BLOCK_TEXTURES_FOLDER))
+        .filter(|folder| asset_io.is_dir(folder))
+        .collect()
+}
+
+fn file_name(asset_server: &AssetServer, id: HandleId) -> Option<String> {
+    let path = asset_server.get_handle_path(id)?;
+    Some(path.path().file_name()?.to_string_lossy().into_owned())
+}
+
+/// Splits an animation strip into its first frame image and the animation state.
+fn animation_strip(name: &str, image: &Image, animation: &TextureAnimation) -> Option<(Image, AnimatedTexture)> {
+    let image = image.convert(TextureFormat::Rgba8UnormSrgb)?;
+    let size = image.texture_descriptor.size;
+    let frame_count = animation.frame_count.unwrap_or(size.height / size.width.max(1));
+    if frame_count == 0 || size.height % frame_count != 0 || animation.frame_time <= 0.0 {
+        warn!("Block texture {name} is not a strip of {frame_count} frames");
+        return None;
+    }
+    let frame_height = size.height / frame_count;
+    let frame_len = (size.width * frame_height * 4) as usize;
+
+    let first_frame = Image::new(
+        Extent3d { width: size.width, height: frame_height, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        image.data[..frame_len].to_vec(),
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    let animation = AnimatedTexture {
+        region: URect::new(0, 0, size.width, frame_height),
+        strip: image.data,
+        frame_count,
+        frame_time: animation.frame_time,
+        interpolate: animation.interpolate,
+        shown_frame: None,
+    };
+    Some((first_frame, animation))
+}
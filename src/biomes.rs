@@ -0,0 +1,88 @@
+//! Per column climate and the colormaps biome tinted blocks are colored with.
+
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use noise::{NoiseFn, OpenSimplex};
+
+/// Horizontal distance in blocks over which the climate changes noticeably.
+const CLIMATE_SCALE: f64 = 256.0;
+
+/// Colormap a tinted block face takes its color from.
+#[derive(serde::Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Colormap {
+    Grass,
+    Foliage,
+}
+
+struct Climate {
+    temperature: OpenSimplex,
+    humidity: OpenSimplex,
+}
+
+impl Climate {
+    /// Temperature and humidity of a column, both from 0 to 1.
+    fn at(&self, x: f32, z: f32) -> (f32, f32) {
+        let point = [x as f64 / CLIMATE_SCALE, z as f64 / CLIMATE_SCALE];
+        let sample = |noise: &OpenSimplex| (noise.get(point) as f32 * 0.5 + 0.5).clamp(0.0, 1.0);
+        (sample(&self.temperature), sample(&self.humidity))
+    }
+}
+
+/// Linear colors of a colormap image, x goes from hot to cold and y from wet to dry.
+struct ColormapImage {
+    width: u32,
+    height: u32,
+    colors: Vec<[f32; 4]>,
+}
+
+impl ColormapImage {
+    fn new(image: &Image) -> Option<Self> {
+        let size = image.texture_descriptor.size;
+        let image = image.convert(TextureFormat::Rgba8UnormSrgb)?;
+        let colors = image.data.chunks_exact(4)
+            .map(|pixel| Color::rgba_u8(pixel[0], pixel[1], pixel[2], 255).as_linear_rgba_f32())
+            .collect();
+        Some(Self { width: size.width, height: size.height, colors })
+    }
+
+    fn sample(&self, temperature: f32, humidity: f32) -> [f32; 4] {
+        // the colormaps are triangles, humidity can't exceed temperature
+        let humidity = humidity * temperature;
+        let x = ((1.0 - temperature) * (self.width - 1) as f32).round() as u32;
+        let y = ((1.0 - humidity) * (self.height - 1) as f32).round() as u32;
+        self.colors[(y * self.width + x) as usize]
+    }
+}
+
+/// Colors biome tinted blocks by the climate of their column, see [`Colormap`].
+#[derive(Resource)]
+pub struct BiomeTint {
+    climate: Climate,
+    grass: ColormapImage,
+    foliage: ColormapImage,
+}
+
+impl BiomeTint {
+    pub fn new(grass: &Image, foliage: &Image) -> Option<Self> {
+        Some(Self {
+            climate: Climate { temperature: OpenSimplex::new(1), humidity: OpenSimplex::new(2) },
+            grass: ColormapImage::new(grass)?,
+            foliage: ColormapImage::new(foliage)?,
+        })
+    }
+
+    /// Temperature of a column from 0 to 1, rain falls as snow in cold columns.
+    pub fn temperature(&self, x: f32, z: f32) -> f32 {
+        self.climate.at(x, z).0
+    }
+
+    /// Vertex color at world position `x`, `z`, it changes smoothly from column to column.
+    pub fn color(&self, colormap: Colormap, x: f32, z: f32) -> [f32; 4] {
+        let (temperature, humidity) = self.climate.at(x, z);
+        match colormap {
+            Colormap::Grass => self.grass.sample(temperature, humidity),
+            Colormap::Foliage => self.foliage.sample(temperature, humidity),
+        }
+    }
+}
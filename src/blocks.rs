@@ -0,0 +1,204 @@
+//! Block definitions loaded from `assets/default.blocks.json`.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use block_mesh::VoxelVisibility;
+
+use crate::biomes::Colormap;
+use crate::items::{ToolKind, ToolTier};
+use crate::models::BlockModel;
+
+/// Index of a block in the [`BlockRegistry`], stored in every voxel.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct BlockId(pub u16);
+
+impl BlockId {
+    /// Air is always the first block, it isn't declared in the asset.
+    pub const AIR: BlockId = BlockId(0);
+}
+
+/// How a block is seen through, also decides the chunk sub-mesh it is rendered in.
+#[derive(serde::Deserialize, Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockVisibility {
+    Empty,
+    #[default]
+    Opaque,
+    /// Alpha tested, faces between two cutout blocks are kept, e.g. leaves.
+    Cutout,
+    /// Alpha blended, e.g. water, glass and ice.
+    Translucent,
+}
+
+/// Texture file names in the block atlas, the face specific ones take priority over `all`.
+#[derive(serde::Deserialize, Default)]
+pub struct BlockTextures {
+    #[serde(default)]
+    pub all: Option<String>,
+    #[serde(default)]
+    pub top: Option<String>,
+    #[serde(default)]
+    pub bottom: Option<String>,
+    #[serde(default)]
+    pub side: Option<String>,
+    /// Drawn over the sides with the block tint, e.g. the grass fringe of grass blocks.
+    #[serde(default)]
+    pub side_overlay: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BlockTint {
+    pub colormap: Colormap,
+    /// Only tint the top face, like grass blocks.
+    #[serde(default)]
+    pub top_only: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_hardness() -> f32 {
+    1.0
+}
+
+#[derive(serde::Deserialize)]
+pub struct BlockDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub textures: BlockTextures,
+    #[serde(default)]
+    pub visibility: BlockVisibility,
+    #[serde(default)]
+    pub model: BlockModel,
+    #[serde(default)]
+    pub tint: Option<BlockTint>,
+    /// Seconds to break the block by hand, tools divide it by their speed.
+    #[serde(default = "default_hardness")]
+    pub hardness: f32,
+    /// Tool that digs the block faster.
+    #[serde(default)]
+    pub tool: Option<ToolKind>,
+    /// Lowest tier of `tool` the block drops an item with, without it any tool or the hand will do.
+    #[serde(default)]
+    pub tool_tier: Option<ToolTier>,
+    #[serde(default = "default_true")]
+    pub collision: bool,
+    /// Whether the player can target the block to dig it or build against it, fluids can't be.
+    #[serde(default = "default_true")]
+    pub selectable: bool,
+    /// Block light level from 0 to 15.
+    #[serde(default)]
+    pub light_emission: u8,
+    /// Rendered with the emissive material so it glows and blooms whatever the light around it.
+    #[serde(default)]
+    pub emissive: bool,
+    /// Rendered with the water material on its own sub-mesh.
+    #[serde(default)]
+    pub water: bool,
+}
+
+impl BlockDefinition {
+    fn air() -> Self {
+        Self {
+            name: "air".to_string(),
+            textures: BlockTextures::default(),
+            visibility: BlockVisibility::Empty,
+            model: BlockModel::Cube,
+            tint: None,
+            hardness: 0.0,
+            tool: None,
+            tool_tier: None,
+            collision: false,
+            selectable: false,
+            light_emission: 0,
+            emissive: false,
+            water: false,
+        }
+    }
+
+    pub fn texture(&self, normal: Vec3) -> &str {
+        let face = if normal.y > 0.5 {
+            &self.textures.top
+        } else if normal.y < -0.5 {
+            &self.textures.bottom
+        } else {
+            &self.textures.side
+        };
+        face.as_deref()
+            .or(self.textures.all.as_deref())
+            .unwrap_or("debug.png")
+    }
+
+    /// Whether sky and block light stop at this block.
+    pub fn blocks_light(&self) -> bool {
+        self.visibility == BlockVisibility::Opaque && self.model == BlockModel::Cube
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visibility != BlockVisibility::Empty
+    }
+
+    /// Colormap the face is tinted with, if any.
+    pub fn tint(&self, normal: Vec3) -> Option<Colormap> {
+        match &self.tint {
+            Some(tint) if !tint.top_only || normal.y > 0.5 => Some(tint.colormap),
+            _ => None,
+        }
+    }
+
+    /// Visibility for block-mesh, models are meshed separately and never hide their neighbours' faces.
+    pub fn voxel_visibility(&self) -> VoxelVisibility {
+        match self.visibility {
+            _ if self.model != BlockModel::Cube => VoxelVisibility::Empty,
+            BlockVisibility::Empty => VoxelVisibility::Empty,
+            BlockVisibility::Opaque => VoxelVisibility::Opaque,
+            BlockVisibility::Cutout => VoxelVisibility::Always,
+            BlockVisibility::Translucent => VoxelVisibility::Translucent,
+        }
+    }
+}
+
+/// Contents of a `.blocks.json` asset.
+#[derive(serde::Deserialize, bevy::reflect::TypeUuid, bevy::reflect::TypePath)]
+#[uuid = "9b0b6f2e-5a4c-4d0e-8f3b-1c7a2d6e4b91"]
+pub struct BlockList {
+    blocks: Vec<BlockDefinition>,
+}
+
+#[derive(Resource, Default)]
+pub struct BlockRegistry {
+    blocks: Vec<BlockDefinition>,
+    ids: HashMap<String, BlockId>,
+}
+
+impl BlockRegistry {
+    pub fn new(list: BlockList) -> Self {
+        let mut blocks = vec![BlockDefinition::air()];
+        blocks.extend(list.blocks);
+        let ids = blocks.iter().enumerate()
+            .map(|(index, block)| (block.name.clone(), BlockId(index as u16)))
+            .collect();
+        Self { blocks, ids }
+    }
+
+    pub fn get(&self, id: BlockId) -> &BlockDefinition {
+        &self.blocks[id.0 as usize]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BlockDefinition> {
+        self.blocks.iter()
+    }
+
+    /// Looks a block up by name, unknown names resolve to air.
+    pub fn id(&self, name: &str) -> BlockId {
+        match self.ids.get(name) {
+            Some(id) => *id,
+            None => {
+                warn!("Unknown block {name}");
+                BlockId::AIR
+            }
+        }
+    }
+}
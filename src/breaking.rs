@@ -0,0 +1,181 @@
+//! Hold-to-break digging: the targeted block breaks after its hardness in seconds, divided by
+//! the speed of the held tool. Progress resets when the button is released or the target
+//! changes, and cracks grow on the outline cube meanwhile.
+
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+};
+
+use bevy_fps_controller::controller::LogicalPlayer;
+
+use crate::blocks::BlockRegistry;
+use crate::console::ConsoleState;
+use crate::drops::DropItem;
+use crate::inventory::{GameMode, Inventory, InventoryScreen};
+use crate::items::{Item, ItemStack, Tool};
+use crate::terrain::VoxelWorld;
+use crate::{cast_ray, DigEvent, DigEventType, GameState, OutlineCube};
+
+const CRACK_STAGES: usize = 10;
+
+pub struct BreakingPlugin;
+
+impl Plugin for BreakingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DigSettings>()
+            .init_resource::<TargetBlock>()
+            .init_resource::<Breaking>()
+            .add_systems(Startup, load_crack_materials)
+            .add_systems(Update, (
+                attach_crack_overlay,
+                (break_block, update_crack_overlay).chain().after(cast_ray),
+            ).run_if(in_state(GameState::InGame)));
+    }
+}
+
+#[derive(Resource)]
+pub struct DigSettings {
+    /// Seconds after breaking a block before the next one starts breaking while the button is held.
+    pub delay: f32,
+    /// Multiplier of every dig speed, whatever the held item.
+    pub speed: f32,
+}
+
+impl Default for DigSettings {
+    fn default() -> Self {
+        Self { delay: 0.25, speed: 1.0 }
+    }
+}
+
+/// Voxel the player is looking at, set by the ray cast.
+#[derive(Resource, Default)]
+pub struct TargetBlock(pub Option<IVec3>);
+
+#[derive(Resource, Default)]
+struct Breaking {
+    voxel: Option<IVec3>,
+    /// From 0 to 1, the block breaks at 1.
+    progress: f32,
+    /// Seconds left before digging can start again.
+    cooldown: f32,
+}
+
+#[derive(Resource)]
+struct CrackMaterials([Handle<StandardMaterial>; CRACK_STAGES]);
+
+#[derive(Component)]
+struct CrackOverlay;
+
+fn load_crack_materials(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let stages = std::array::from_fn(|stage| materials.add(StandardMaterial {
+        base_color_texture: Some(asset_server.load(format!("textures/destroy/stage_{stage}.png"))),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    }));
+    commands.insert_resource(CrackMaterials(stages));
+}
+
+fn attach_crack_overlay(
+    mut commands: Commands,
+    outline_cubes: Query<Entity, Added<OutlineCube>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    crack_materials: Res<CrackMaterials>,
+) {
+    for outline_cube in &outline_cubes {
+        commands.entity(outline_cube).with_children(|builder| {
+            builder.spawn((
+                PbrBundle {
+                    // slightly larger than the block so it doesn't fight with its faces
+                    mesh: meshes.add(Mesh::from(shape::Cube { size: 1.004 })),
+                    material: crack_materials.0[0].clone(),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                CrackOverlay,
+                NotShadowCaster,
+                NotShadowReceiver,
+            ));
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn break_block(
+    time: Res<Time>,
+    btn: Res<Input<MouseButton>>,
+    target: Res<TargetBlock>,
+    voxel_world: VoxelWorld,
+    registry: Res<BlockRegistry>,
+    settings: Res<DigSettings>,
+    game_mode: Res<GameMode>,
+    screen: Res<InventoryScreen>,
+    console: Res<ConsoleState>,
+    mut players: Query<&mut Inventory, With<LogicalPlayer>>,
+    mut breaking: ResMut<Breaking>,
+    mut ev: EventWriter<DigEvent>,
+    mut drops: EventWriter<DropItem>,
+) {
+    let delta = time.delta_seconds();
+    breaking.cooldown = (breaking.cooldown - delta).max(0.0);
+
+    let digging = btn.pressed(MouseButton::Left) && !screen.is_open() && !console.is_open();
+    let (Some(voxel), true) = (target.0, digging) else {
+        breaking.voxel = None;
+        breaking.progress = 0.0;
+        return;
+    };
+    if breaking.voxel != Some(voxel) {
+        breaking.voxel = Some(voxel);
+        breaking.progress = 0.0;
+    }
+    if breaking.cooldown > 0.0 {
+        return;
+    }
+    let (Some(block_id), Ok(mut inventory)) = (voxel_world.block_id_at(voxel.as_vec3()), players.get_single_mut()) else {
+        return;
+    };
+    let block = registry.get(block_id);
+
+    let tool = inventory.selected_tool();
+    let seconds = Tool::dig_seconds(tool, block) / settings.speed;
+    breaking.progress = if seconds > 0.0 { breaking.progress + delta / seconds } else { 1.0 };
+    if breaking.progress >= 1.0 {
+        let center = voxel.as_vec3() + Vec3::splat(0.5);
+        if *game_mode == GameMode::Survival {
+            // instantly broken blocks like plants don't wear tools out
+            if block.hardness > 0.0 {
+                inventory.wear_selected();
+            }
+            if Tool::drops(tool, block) {
+                drops.send(DropItem { stack: ItemStack::new(Item::Block(block_id), 1), position: center });
+            }
+        }
+        ev.send(DigEvent { event_type: DigEventType::Dig, world_position: center });
+        breaking.voxel = None;
+        breaking.progress = 0.0;
+        breaking.cooldown = settings.delay;
+    }
+}
+
+fn update_crack_overlay(
+    breaking: Res<Breaking>,
+    crack_materials: Res<CrackMaterials>,
+    mut overlays: Query<(&mut Handle<StandardMaterial>, &mut Visibility), With<CrackOverlay>>,
+) {
+    for (mut material, mut visibility) in &mut overlays {
+        if breaking.voxel.is_some() && breaking.progress > 0.0 {
+            let stage = ((breaking.progress * CRACK_STAGES as f32) as usize).min(CRACK_STAGES - 1);
+            *material = crack_materials.0[stage].clone();
+            *visibility = Visibility::Inherited;
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
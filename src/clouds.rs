@@ -0,0 +1,285 @@
+//! Cloud layer: a tile of cloud cells from tileable 2D noise, repeated in a 3x3 grid around the
+//! camera at a fixed altitude and moved by the wind. Cells are flat quads or extruded boxes.
+
+use std::f64::consts::TAU;
+
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::{
+        mesh::Indices,
+        render_resource::{AsBindGroup, PrimitiveTopology, ShaderRef},
+    },
+};
+use noise::{Fbm, NoiseFn, OpenSimplex};
+
+use crate::console::{ConsoleCommand, ConsoleReply};
+use crate::fog::SkyFog;
+use crate::weather::Weather;
+use crate::world_time::{mix_colors, WorldTime};
+
+/// Cloud cells along each side of the tile.
+const CLOUD_CELLS: i32 = 64;
+const CELL_SIZE: f32 = 12.0;
+const TILE_SIZE: f32 = CLOUD_CELLS as f32 * CELL_SIZE;
+const CLOUD_THICKNESS: f32 = 4.0;
+/// Noise value above which a cell is cloudy, higher values give fewer clouds.
+const COVERAGE_THRESHOLD: f64 = 0.1;
+/// Clouds are about this many cells wide.
+const CLOUD_FEATURE_CELLS: f64 = 5.0;
+
+pub struct CloudsPlugin;
+
+impl Plugin for CloudsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(MaterialPlugin::<CloudMaterial>::default())
+            .init_resource::<CloudSettings>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, ((clouds_command, rebuild_clouds).chain(), move_clouds, update_cloud_color));
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum CloudStyle {
+    Flat,
+    Extruded,
+}
+
+#[derive(Resource)]
+pub struct CloudSettings {
+    pub style: CloudStyle,
+    /// Height of the bottom of the clouds.
+    pub altitude: f32,
+    /// Blocks per second the clouds move.
+    pub wind: Vec2,
+}
+
+impl Default for CloudSettings {
+    fn default() -> Self {
+        Self { style: CloudStyle::Extruded, altitude: 96.0, wind: Vec2::new(1.5, 0.4) }
+    }
+}
+
+#[derive(AsBindGroup, TypeUuid, TypePath, Clone)]
+#[uuid = "0c8a5e31-47d2-4b9f-a6e3-d91b2f7c4a58"]
+pub struct CloudMaterial {
+    #[uniform(0)]
+    color: Color,
+    /// Horizontal distance from the camera where clouds start to fade out.
+    #[uniform(0)]
+    fade_start: f32,
+    /// Horizontal distance from the camera where clouds are gone.
+    #[uniform(0)]
+    fade_end: f32,
+}
+
+impl Material for CloudMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/clouds.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+}
+
+#[derive(Component)]
+struct Clouds {
+    /// Distance the wind moved the clouds, wrapped to the tile.
+    offset: Vec2,
+    /// Shared by every tile of the grid.
+    mesh: Handle<Mesh>,
+    material: Handle<CloudMaterial>,
+}
+
+/// Whether each cell of the tile is cloudy. The noise is sampled on a torus,
+/// so the tile wraps around without seams.
+fn cloud_cells() -> Vec<bool> {
+    let noise = Fbm::<OpenSimplex>::new(3);
+    let radius = CLOUD_CELLS as f64 / (TAU * CLOUD_FEATURE_CELLS);
+    let mut cells = Vec::with_capacity((CLOUD_CELLS * CLOUD_CELLS) as usize);
+    for z in 0..CLOUD_CELLS {
+        for x in 0..CLOUD_CELLS {
+            let angle_x = x as f64 / CLOUD_CELLS as f64 * TAU;
+            let angle_z = z as f64 / CLOUD_CELLS as f64 * TAU;
+            let point = [
+                angle_x.cos() * radius,
+                angle_x.sin() * radius,
+                angle_z.cos() * radius,
+                angle_z.sin() * radius,
+            ];
+            cells.push(noise.get(point) > COVERAGE_THRESHOLD);
+        }
+    }
+    cells
+}
+
+/// Corners of a box face, counter-clockwise seen from outside.
+fn box_face(min: Vec3, max: Vec3, normal: IVec3) -> [Vec3; 4] {
+    let (x0, y0, z0) = (min.x, min.y, min.z);
+    let (x1, y1, z1) = (max.x, max.y, max.z);
+    match (normal.x, normal.y, normal.z) {
+        (0, 1, 0) => [Vec3::new(x0, y1, z0), Vec3::new(x0, y1, z1), Vec3::new(x1, y1, z1), Vec3::new(x1, y1, z0)],
+        (0, -1, 0) => [Vec3::new(x0, y0, z0), Vec3::new(x1, y0, z0), Vec3::new(x1, y0, z1), Vec3::new(x0, y0, z1)],
+        (1, 0, 0) => [Vec3::new(x1, y0, z0), Vec3::new(x1, y1, z0), Vec3::new(x1, y1, z1), Vec3::new(x1, y0, z1)],
+        (-1, 0, 0) => [Vec3::new(x0, y0, z0), Vec3::new(x0, y0, z1), Vec3::new(x0, y1, z1), Vec3::new(x0, y1, z0)],
+        (0, 0, 1) => [Vec3::new(x0, y0, z1), Vec3::new(x1, y0, z1), Vec3::new(x1, y1, z1), Vec3::new(x0, y1, z1)],
+        _ => [Vec3::new(x0, y0, z0), Vec3::new(x0, y1, z0), Vec3::new(x1, y1, z0), Vec3::new(x1, y0, z0)],
+    }
+}
+
+fn cloud_mesh(style: CloudStyle) -> Mesh {
+    let cells = cloud_cells();
+    let cloudy = |x: i32, z: i32| cells[(x.rem_euclid(CLOUD_CELLS) + z.rem_euclid(CLOUD_CELLS) * CLOUD_CELLS) as usize];
+
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for z in 0..CLOUD_CELLS {
+        for x in 0..CLOUD_CELLS {
+            if !cloudy(x, z) {
+                continue;
+            }
+            let min = Vec3::new(x as f32 * CELL_SIZE, 0.0, z as f32 * CELL_SIZE);
+            let max = match style {
+                CloudStyle::Flat => min + Vec3::new(CELL_SIZE, 0.0, CELL_SIZE),
+                CloudStyle::Extruded => min + Vec3::new(CELL_SIZE, CLOUD_THICKNESS, CELL_SIZE),
+            };
+
+            let mut faces = vec![IVec3::Y, IVec3::NEG_Y];
+            if style == CloudStyle::Extruded {
+                // sides between two cloudy cells are inside the cloud, neighbours wrap like the tile
+                faces.extend([IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z]
+                    .into_iter()
+                    .filter(|normal| !cloudy(x + normal.x, z + normal.z)));
+            }
+            for normal in faces {
+                let start = positions.len() as u32;
+                positions.extend(box_face(min, max, normal).map(|corner| corner.to_array()));
+                normals.extend([normal.as_vec3().to_array(); 4]);
+                indices.extend([0, 1, 2, 0, 2, 3].map(|index| start + index));
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn setup(
+    mut commands: Commands,
+    settings: Res<CloudSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+) {
+    let now = std::time::SystemTime::now();
+    let mesh = meshes.add(cloud_mesh(settings.style));
+    println!("Cloud gen time: {}ms", now.elapsed().unwrap().as_millis());
+
+    let material = materials.add(CloudMaterial {
+        color: Color::WHITE,
+        fade_start: TILE_SIZE * 0.2,
+        fade_end: TILE_SIZE * 0.45,
+    });
+
+    // a 3x3 grid around the tile under the camera reaches past the fade radius on every side
+    commands
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(0.0, settings.altitude, 0.0)),
+            Clouds { offset: Vec2::ZERO, mesh: mesh.clone(), material: material.clone() },
+        ))
+        .with_children(|parent| {
+            for z in -1..=1 {
+                for x in -1..=1 {
+                    parent.spawn((
+                        MaterialMeshBundle {
+                            mesh: mesh.clone(),
+                            material: material.clone(),
+                            transform: Transform::from_xyz(x as f32 * TILE_SIZE, 0.0, z as f32 * TILE_SIZE),
+                            ..default()
+                        },
+                        NotShadowCaster,
+                        NotShadowReceiver,
+                    ));
+                }
+            }
+        });
+}
+
+fn rebuild_clouds(
+    settings: Res<CloudSettings>,
+    clouds: Query<&Clouds>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+    for clouds in &clouds {
+        if let Some(mesh) = meshes.get_mut(&clouds.mesh) {
+            *mesh = cloud_mesh(settings.style);
+        }
+    }
+}
+
+fn move_clouds(
+    time: Res<Time>,
+    settings: Res<CloudSettings>,
+    camera: Query<&GlobalTransform, With<Camera3d>>,
+    mut clouds: Query<(&mut Transform, &mut Clouds)>,
+) {
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+    let camera = camera.translation().xz();
+    for (mut transform, mut clouds) in &mut clouds {
+        let offset = clouds.offset + settings.wind * time.delta_seconds();
+        clouds.offset = Vec2::new(offset.x.rem_euclid(TILE_SIZE), offset.y.rem_euclid(TILE_SIZE));
+
+        // the tiles repeat, so the grid is moved by whole tiles to stay centered on the camera
+        let tile = ((camera - clouds.offset) / TILE_SIZE).round() * TILE_SIZE + clouds.offset - TILE_SIZE / 2.0;
+        transform.translation = Vec3::new(tile.x, settings.altitude, tile.y);
+    }
+}
+
+fn update_cloud_color(
+    time: Res<WorldTime>,
+    weather: Res<Weather>,
+    fog: Res<SkyFog>,
+    clouds: Query<&Clouds>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+) {
+    let day = mix_colors(Color::WHITE, Color::rgb(1.0, 0.7, 0.55), time.twilight());
+    let color = mix_colors(Color::rgb(0.08, 0.09, 0.13), day, time.daylight());
+    // rain clouds are dark
+    let brightness = weather.sunlight() * (1.0 - 0.4 * weather.overcast());
+    // the fogged sky hides them from under water
+    let alpha = if fog.underwater { 0.0 } else { 0.85 };
+    for clouds in &clouds {
+        if let Some(material) = materials.get_mut(&clouds.material) {
+            material.color = Color::rgba(color.r() * brightness, color.g() * brightness, color.b() * brightness, alpha);
+        }
+    }
+}
+
+fn clouds_command(
+    mut commands: EventReader<ConsoleCommand>,
+    mut replies: EventWriter<ConsoleReply>,
+    mut settings: ResMut<CloudSettings>,
+) {
+    for command in commands.iter().filter(|command| command.name == "clouds") {
+        settings.style = match command.args.first().map(String::as_str) {
+            Some("flat") => CloudStyle::Flat,
+            Some("extruded") => CloudStyle::Extruded,
+            _ => {
+                replies.send(ConsoleReply("Usage: /clouds <flat|extruded>".to_string()));
+                continue;
+            }
+        };
+        replies.send(ConsoleReply(format!("Clouds are {}", command.args[0])));
+    }
+}
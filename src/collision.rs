@@ -0,0 +1,88 @@
+//! Chunk colliders built from voxel data: solid cubes are merged greedily into boxes,
+//! other models add their own collision boxes.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use block_mesh::ndshape::Shape;
+
+use crate::blocks::{BlockId, BlockRegistry};
+use crate::models::BlockModel;
+
+/// Builds the compound collider of the interior voxels of a padded chunk,
+/// positions are in chunk samples like the render meshes.
+pub fn chunk_collider<S: Shape<3, Coord = u32>>(
+    blocks: impl Fn(u32) -> BlockId,
+    shape: &S,
+    registry: &BlockRegistry,
+) -> Option<Collider> {
+    let [size_x, size_y, size_z] = shape.as_array().map(|size| size - 2);
+    let interior = |[x, y, z]: [u32; 3]| (x + size_x * (y + size_y * z)) as usize;
+
+    // solid cubes not yet covered by a box
+    let mut solid = vec![false; (size_x * size_y * size_z) as usize];
+    let mut boxes: Vec<(Vec3, Vec3)> = Vec::new();
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let block = registry.get(blocks(shape.linearize([x + 1, y + 1, z + 1])));
+                if !block.collision {
+                    continue;
+                }
+                if block.model == BlockModel::Cube {
+                    solid[interior([x, y, z])] = true;
+                } else {
+                    let origin = Vec3::new((x + 1) as f32, (y + 1) as f32, (z + 1) as f32);
+                    boxes.extend(block.model.collision_boxes().iter().map(|(min, max)| (origin + *min, origin + *max)));
+                }
+            }
+        }
+    }
+
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                if !solid[interior([x, y, z])] {
+                    continue;
+                }
+
+                // grow along x, then whole rows along y, then whole slabs along z
+                let mut end_x = x + 1;
+                while end_x < size_x && solid[interior([end_x, y, z])] {
+                    end_x += 1;
+                }
+                let row_solid = |y: u32, z: u32| (x..end_x).all(|x| solid[interior([x, y, z])]);
+                let mut end_y = y + 1;
+                while end_y < size_y && row_solid(end_y, z) {
+                    end_y += 1;
+                }
+                let mut end_z = z + 1;
+                while end_z < size_z && (y..end_y).all(|y| row_solid(y, end_z)) {
+                    end_z += 1;
+                }
+
+                for box_z in z..end_z {
+                    for box_y in y..end_y {
+                        for box_x in x..end_x {
+                            solid[interior([box_x, box_y, box_z])] = false;
+                        }
+                    }
+                }
+                boxes.push((
+                    Vec3::new((x + 1) as f32, (y + 1) as f32, (z + 1) as f32),
+                    Vec3::new((end_x + 1) as f32, (end_y + 1) as f32, (end_z + 1) as f32),
+                ));
+            }
+        }
+    }
+
+    if boxes.is_empty() {
+        return None;
+    }
+    let shapes = boxes.into_iter()
+        .map(|(min, max)| {
+            let half_extents = (max - min) / 2.0;
+            (min + half_extents, Quat::IDENTITY, Collider::cuboid(half_extents.x, half_extents.y, half_extents.z))
+        })
+        .collect();
+    Some(Collider::compound(shapes))
+}
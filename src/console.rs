@@ -0,0 +1,140 @@
+//! Chat style command line: `/` opens it, Enter runs the typed command and Escape closes it.
+//! Other plugins read [`ConsoleCommand`] events and answer with [`ConsoleReply`].
+
+use bevy::prelude::*;
+use bevy_fps_controller::controller::FpsController;
+
+/// Replies kept on screen.
+const HISTORY_LINES: usize = 6;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ConsoleState>()
+            .add_event::<ConsoleCommand>()
+            .add_event::<ConsoleReply>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, (console_input, collect_replies, update_console_text).chain());
+    }
+}
+
+/// A submitted command, `/time set 12` has the name `time` and the arguments `set` and `12`.
+#[derive(Event)]
+pub struct ConsoleCommand {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Event)]
+pub struct ConsoleReply(pub String);
+
+#[derive(Resource, Default)]
+pub struct ConsoleState {
+    open: bool,
+    input: String,
+    history: Vec<String>,
+}
+
+impl ConsoleState {
+    /// Keys typed into the console shouldn't also move the player or pick blocks.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            // above the hotbar
+            bottom: Val::Px(70.0),
+            ..default()
+        }),
+        ConsoleText,
+    ));
+}
+
+pub fn console_input(
+    key: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut state: ResMut<ConsoleState>,
+    mut controllers: Query<&mut FpsController>,
+    mut ev: EventWriter<ConsoleCommand>,
+) {
+    if !state.open {
+        if key.just_pressed(KeyCode::Slash) {
+            state.open = true;
+            state.input = "/".to_string();
+            for mut controller in controllers.iter_mut() {
+                controller.enable_input = false;
+            }
+            // the slash that opened the console arrives as a character too
+            characters.clear();
+        }
+        return;
+    }
+
+    for character in characters.iter() {
+        if !character.char.is_control() {
+            state.input.push(character.char);
+        }
+    }
+    if key.just_pressed(KeyCode::Back) {
+        state.input.pop();
+    }
+    if key.just_pressed(KeyCode::Escape) {
+        state.input.clear();
+        state.open = false;
+        for mut controller in controllers.iter_mut() {
+            controller.enable_input = true;
+        }
+    }
+    if key.just_pressed(KeyCode::Return) {
+        let input = std::mem::take(&mut state.input);
+        let mut words = input.trim_start_matches('/').split_whitespace().map(str::to_string);
+        if let Some(name) = words.next() {
+            ev.send(ConsoleCommand { name, args: words.collect() });
+        }
+        state.open = false;
+        for mut controller in controllers.iter_mut() {
+            controller.enable_input = true;
+        }
+    }
+}
+
+fn collect_replies(mut replies: EventReader<ConsoleReply>, mut state: ResMut<ConsoleState>) {
+    for reply in replies.iter() {
+        state.history.push(reply.0.clone());
+    }
+    if state.history.len() > HISTORY_LINES {
+        let excess = state.history.len() - HISTORY_LINES;
+        state.history.drain(..excess);
+    }
+}
+
+fn update_console_text(state: Res<ConsoleState>, mut query: Query<&mut Text, With<ConsoleText>>) {
+    if !state.is_changed() {
+        return;
+    }
+    let mut lines = state.history.clone();
+    if state.open {
+        lines.push(format!("{}_", state.input));
+    }
+    for mut text in &mut query {
+        text.sections[0].value = lines.join("\n");
+    }
+}
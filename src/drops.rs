@@ -0,0 +1,190 @@
+//! Dropped items: dug blocks pop out as small rapier bodies that fall, bob and spin on the
+//! ground, merge with identical drops nearby and are collected when the player walks over them.
+
+use bevy::prelude::*;
+use bevy::pbr::NotShadowReceiver;
+use bevy_fps_controller::controller::LogicalPlayer;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::blocks::BlockRegistry;
+use crate::inventory::Inventory;
+use crate::items::{Item, ItemStack};
+use crate::GameState;
+
+/// Dropped items only collide with the terrain, neither with the player nor with each other.
+pub const ITEM_GROUP: Group = Group::GROUP_2;
+
+/// Size of a dropped block.
+const ITEM_SIZE: f32 = 0.25;
+/// Seconds before a new drop can be picked up, so it is seen popping out.
+const PICKUP_DELAY: f32 = 0.5;
+const PICKUP_DISTANCE: f32 = 1.5;
+const MERGE_DISTANCE: f32 = 1.0;
+/// Seconds a drop lies around before it disappears, like in Minecraft.
+const DESPAWN_SECONDS: f32 = 300.0;
+
+pub struct DropsPlugin;
+
+impl Plugin for DropsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<DropItem>()
+            .add_systems(Update, (
+                spawn_drops,
+                age_drops,
+                merge_drops,
+                pick_up_drops,
+                bob_drops,
+            ).chain().run_if(in_state(GameState::InGame)));
+    }
+}
+
+/// Spawns a stack of items on the ground.
+#[derive(Event)]
+pub struct DropItem {
+    pub stack: ItemStack,
+    pub position: Vec3,
+}
+
+/// Meshes of dropped blocks indexed by block id, `None` for invisible blocks.
+#[derive(Resource)]
+pub struct ItemMeshes {
+    pub meshes: Vec<Option<Handle<Mesh>>>,
+    pub material: Handle<StandardMaterial>,
+    pub emissive_material: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct DroppedItem {
+    stack: ItemStack,
+    /// Seconds since the drop was spawned.
+    age: f32,
+}
+
+/// The visible block of a drop, a child of its rapier body.
+#[derive(Component)]
+struct DroppedItemModel;
+
+fn spawn_drops(
+    mut commands: Commands,
+    mut events: EventReader<DropItem>,
+    item_meshes: Option<Res<ItemMeshes>>,
+    registry: Res<BlockRegistry>,
+) {
+    let Some(item_meshes) = item_meshes else {
+        events.clear();
+        return;
+    };
+    let mut rng = rand::thread_rng();
+    for event in events.iter() {
+        // only blocks have a model to drop
+        let Item::Block(block) = event.stack.item else {
+            continue;
+        };
+        let Some(mesh) = item_meshes.meshes.get(block.0 as usize).cloned().flatten() else {
+            continue;
+        };
+        let material = if registry.get(block).emissive {
+            item_meshes.emissive_material.clone()
+        } else {
+            item_meshes.material.clone()
+        };
+
+        let half_size = ITEM_SIZE / 2.0;
+        let pop = Vec3::new(rng.gen_range(-1.0..1.0), 3.0, rng.gen_range(-1.0..1.0));
+        commands
+            .spawn((
+                DroppedItem { stack: event.stack, age: 0.0 },
+                SpatialBundle::from_transform(Transform::from_translation(event.position)),
+                RigidBody::Dynamic,
+                Collider::cuboid(half_size, half_size, half_size),
+                CollisionGroups::new(ITEM_GROUP, !ITEM_GROUP),
+                LockedAxes::ROTATION_LOCKED,
+                Velocity::linear(pop),
+                Damping { linear_damping: 0.5, angular_damping: 0.0 },
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    PbrBundle {
+                        mesh,
+                        material,
+                        transform: Transform::from_scale(Vec3::splat(ITEM_SIZE)),
+                        ..default()
+                    },
+                    DroppedItemModel,
+                    NotShadowReceiver,
+                ));
+            });
+    }
+}
+
+fn age_drops(mut commands: Commands, time: Res<Time>, mut drops: Query<(Entity, &mut DroppedItem)>) {
+    for (entity, mut drop) in &mut drops {
+        drop.age += time.delta_seconds();
+        if drop.age > DESPAWN_SECONDS {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn merge_drops(mut commands: Commands, mut drops: Query<(Entity, &Transform, &mut DroppedItem)>) {
+    let mut pairs = drops.iter_combinations_mut();
+    while let Some([(_, transform, mut drop), (other_entity, other_transform, mut other)]) = pairs.fetch_next() {
+        // emptied drops are despawned at the end of the frame
+        if drop.stack.count == 0 || other.stack.count == 0 || drop.stack.item != other.stack.item
+            || transform.translation.distance(other_transform.translation) > MERGE_DISTANCE
+        {
+            continue;
+        }
+        match drop.stack.merge(other.stack) {
+            Some(left) => other.stack = left,
+            None => {
+                other.stack.count = 0;
+                commands.entity(other_entity).despawn_recursive();
+            }
+        }
+        // the merged drop lasts as long as the newer one
+        drop.age = drop.age.min(other.age);
+    }
+}
+
+fn pick_up_drops(
+    mut commands: Commands,
+    mut players: Query<(&Transform, &mut Inventory), With<LogicalPlayer>>,
+    mut drops: Query<(Entity, &Transform, &mut DroppedItem)>,
+) {
+    for (player, mut inventory) in &mut players {
+        // middle of the player capsule
+        let center = player.translation + Vec3::Y;
+        for (entity, transform, mut drop) in &mut drops {
+            if drop.age < PICKUP_DELAY || drop.stack.count == 0
+                || transform.translation.distance(center) > PICKUP_DISTANCE
+            {
+                continue;
+            }
+            // a full inventory leaves the rest on the ground
+            match inventory.add(drop.stack) {
+                Some(left) => drop.stack = left,
+                None => {
+                    drop.stack.count = 0;
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+fn bob_drops(
+    drops: Query<(&DroppedItem, &Children)>,
+    mut models: Query<&mut Transform, With<DroppedItemModel>>,
+) {
+    for (drop, children) in &drops {
+        for child in children.iter() {
+            if let Ok(mut transform) = models.get_mut(*child) {
+                transform.translation.y = (drop.age * 2.5).sin() * 0.05 + 0.05;
+                transform.rotation = Quat::from_rotation_y(drop.age);
+            }
+        }
+    }
+}
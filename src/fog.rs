@@ -0,0 +1,70 @@
+//! Distance fog hiding the edge of the loaded chunks. Its color follows the sky through the
+//! day and the weather, and thick blue fog takes over while the camera is in water.
+
+use bevy::prelude::*;
+
+use crate::terrain::{VoxelWorld, CHUNK_SIZE, VISIBLE_CHUNK_DISTANCE};
+use crate::weather::Weather;
+use crate::world_time::{mix_colors, WorldTime};
+
+/// Fog starts at this fraction of the view distance.
+const FOG_START: f32 = 0.5;
+const UNDERWATER_COLOR: Color = Color::rgb(0.05, 0.18, 0.35);
+const UNDERWATER_DISTANCE: f32 = 14.0;
+
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SkyFog>()
+            .add_systems(Update, (add_fog, update_fog).chain());
+    }
+}
+
+/// Fog the sky fades into at the horizon, `underwater` fogs the whole sky.
+#[derive(Resource, Default)]
+pub struct SkyFog {
+    pub color: Color,
+    pub underwater: bool,
+}
+
+/// Color of the sky at the horizon for the time of day and the weather.
+fn horizon_color(time: &WorldTime, weather: &Weather) -> Color {
+    let day = mix_colors(Color::rgb(0.72, 0.82, 0.95), Color::rgb(0.95, 0.6, 0.4), time.twilight());
+    let clear = mix_colors(Color::rgb(0.02, 0.025, 0.05), day, time.daylight());
+    let grey = Color::rgb(0.45, 0.47, 0.5) * (0.1 + 0.9 * time.daylight());
+    mix_colors(clear, grey, weather.overcast())
+}
+
+fn add_fog(mut commands: Commands, cameras: Query<Entity, (With<Camera3d>, Without<FogSettings>)>) {
+    for camera in &cameras {
+        commands.entity(camera).insert(FogSettings::default());
+    }
+}
+
+fn update_fog(
+    voxel_world: VoxelWorld,
+    time: Res<WorldTime>,
+    weather: Res<Weather>,
+    mut sky_fog: ResMut<SkyFog>,
+    mut cameras: Query<(&GlobalTransform, &mut FogSettings), With<Camera3d>>,
+) {
+    for (transform, mut fog) in &mut cameras {
+        let underwater = voxel_world.block_at(transform.translation()).is_some_and(|block| block.water);
+        let view_distance = (VISIBLE_CHUNK_DISTANCE * CHUNK_SIZE) as f32;
+
+        let (color, falloff) = if underwater {
+            (UNDERWATER_COLOR, FogFalloff::Linear { start: 0.0, end: UNDERWATER_DISTANCE })
+        } else {
+            // rain shortens the view
+            let end = view_distance * (1.0 - 0.4 * weather.overcast());
+            (horizon_color(&time, &weather), FogFalloff::Linear { start: end * FOG_START, end })
+        };
+        fog.color = color;
+        fog.falloff = falloff;
+
+        sky_fog.color = color;
+        sky_fog.underwater = underwater;
+    }
+}
@@ -0,0 +1,481 @@
+//! The player's items: a hotbar row and storage rows of item stacks, the inventory screen toggled
+//! with E to move them around, and the game modes. In survival digging gives the dug block and
+//! building uses up the selected one, in creative blocks never run out.
+
+use std::ops::Range;
+
+use bevy::{prelude::*, window::CursorGrabMode};
+use bevy_fps_controller::controller::{FpsController, LogicalPlayer};
+
+use crate::blocks::{BlockId, BlockRegistry};
+use crate::console::{ConsoleCommand, ConsoleReply, ConsoleState};
+use crate::items::{Item, ItemStack, Tool};
+use crate::ui::{spawn_item_view_parts, ItemView};
+
+pub const HOTBAR_SLOTS: usize = 9;
+/// The hotbar and three rows of storage.
+pub const INVENTORY_SLOTS: usize = HOTBAR_SLOTS * 4;
+
+/// Items given to the player once the block list is loaded.
+const STARTING_ITEMS: [(&str, u32); 9] = [
+    ("stone_pickaxe", 1), ("cobblestone", 64), ("glowstone", 16), ("torch", 32), ("oak_planks", 64),
+    ("glass", 32), ("dirt", 64), ("oak_log", 32), ("sand", 64),
+];
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GameMode>()
+            .init_resource::<InventoryScreen>()
+            .add_systems(Startup, setup_screen)
+            .add_systems(Update, (
+                give_starting_items,
+                give_command,
+                gamemode_command,
+                (toggle_screen, click_slots, update_screen, drag_item).chain(),
+            ));
+    }
+}
+
+#[derive(Resource, Default, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum GameMode {
+    /// Dug blocks are collected, placed blocks are used up and tools wear out.
+    #[default]
+    Survival,
+    Creative,
+}
+
+/// Items carried by the player, the first [`HOTBAR_SLOTS`] slots are the hotbar.
+#[derive(Component)]
+pub struct Inventory {
+    pub slots: [Option<ItemStack>; INVENTORY_SLOTS],
+    /// Selected hotbar slot.
+    pub selected: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self { slots: [None; INVENTORY_SLOTS], selected: 0 }
+    }
+}
+
+impl Inventory {
+    pub fn selected_block(&self) -> Option<BlockId> {
+        match self.slots[self.selected]?.item {
+            Item::Block(block) => Some(block),
+            Item::Tool(_) => None,
+        }
+    }
+
+    pub fn selected_tool(&self) -> Option<Tool> {
+        self.slots[self.selected]?.tool()
+    }
+
+    /// Adds a stack to the hotbar or the storage, returns what didn't fit.
+    pub fn add(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        self.insert(0..INVENTORY_SLOTS, stack)
+    }
+
+    /// Puts a stack onto the stacks of the same item in `range` first, then into an empty slot.
+    fn insert(&mut self, range: Range<usize>, mut stack: ItemStack) -> Option<ItemStack> {
+        // an empty stack would take a slot and never run out
+        if stack.count == 0 {
+            return None;
+        }
+        for slot in self.slots[range.clone()].iter_mut().flatten() {
+            match slot.merge(stack) {
+                Some(left) => stack = left,
+                None => return None,
+            }
+        }
+        match self.slots[range].iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(stack);
+                None
+            }
+            None => Some(stack),
+        }
+    }
+
+    /// Uses up one item of the selected stack, e.g. a placed block.
+    pub fn take_selected(&mut self) {
+        let selected = self.selected;
+        if let Some(stack) = &mut self.slots[selected] {
+            match stack.count.checked_sub(1) {
+                Some(count) if count > 0 => stack.count = count,
+                _ => self.slots[selected] = None,
+            }
+        }
+    }
+
+    /// Wears the selected tool out by one use, it breaks when no durability is left.
+    pub fn wear_selected(&mut self) {
+        let selected = self.selected;
+        if let Some(stack) = &mut self.slots[selected] {
+            if stack.tool().is_some() {
+                stack.durability = stack.durability.saturating_sub(1);
+                if stack.durability == 0 {
+                    self.slots[selected] = None;
+                }
+            }
+        }
+    }
+
+    /// Shift-click moves a stack from the hotbar to the storage and back.
+    fn quick_move(&mut self, slot: usize) {
+        let Some(stack) = self.slots[slot].take() else {
+            return;
+        };
+        let range = if slot < HOTBAR_SLOTS { HOTBAR_SLOTS..INVENTORY_SLOTS } else { 0..HOTBAR_SLOTS };
+        self.slots[slot] = self.insert(range, stack);
+    }
+
+    /// Drops a stack dragged out of slot `from` on slot `to`: it merges with the same items or
+    /// swaps with other ones, and whatever is left goes back to `from`.
+    fn drop_stack(&mut self, from: usize, to: usize, stack: ItemStack) {
+        let left = match &mut self.slots[to] {
+            Some(target) if target.item == stack.item => target.merge(stack),
+            target => target.replace(stack),
+        };
+        let Some(left) = left else {
+            return;
+        };
+        // items picked up while dragging may have taken the slot
+        if self.slots[from].is_none() {
+            self.slots[from] = Some(left);
+        } else if let Some(lost) = self.add(left) {
+            warn!("No room left for {lost:?}");
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct InventoryScreen {
+    open: bool,
+    /// Stack being dragged by the mouse and the slot it was taken from.
+    dragged: Option<(usize, ItemStack)>,
+}
+
+impl InventoryScreen {
+    /// Clicks go to the screen instead of digging and building.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+#[derive(Component)]
+struct InventoryRoot;
+
+/// A slot of the inventory screen, by inventory index.
+#[derive(Component)]
+struct InventorySlot(usize);
+
+/// Follows the mouse with the dragged stack.
+#[derive(Component)]
+struct DraggedItem;
+
+const SLOT_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.4);
+const HOVERED_SLOT_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.25);
+
+fn setup_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+                visibility: Visibility::Hidden,
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            InventoryRoot,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(12.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.15, 0.15, 0.15, 0.9).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Inventory",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                    for row in 1..INVENTORY_SLOTS / HOTBAR_SLOTS {
+                        spawn_slot_row(parent, row * HOTBAR_SLOTS..(row + 1) * HOTBAR_SLOTS, &font);
+                    }
+                    // the hotbar sits apart below the storage
+                    parent.spawn(NodeBundle {
+                        style: Style {
+                            height: Val::Px(12.0),
+                            ..default()
+                        },
+                        ..default()
+                    });
+                    spawn_slot_row(parent, 0..HOTBAR_SLOTS, &font);
+                });
+
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            width: Val::Px(48.0),
+                            height: Val::Px(48.0),
+                            align_items: AlignItems::Center,
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    DraggedItem,
+                    ItemView::default(),
+                ))
+                .with_children(|parent| spawn_item_view_parts(parent, font.clone()));
+        });
+}
+
+/// A row of inventory slots on the screen.
+fn spawn_slot_row(parent: &mut ChildBuilder, slots: Range<usize>, font: &Handle<Font>) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for slot in slots {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(48.0),
+                                height: Val::Px(48.0),
+                                margin: UiRect::all(Val::Px(2.0)),
+                                border: UiRect::all(Val::Px(2.0)),
+                                align_items: AlignItems::Center,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            border_color: Color::rgba(0.3, 0.3, 0.3, 0.8).into(),
+                            background_color: SLOT_COLOR.into(),
+                            ..default()
+                        },
+                        InventorySlot(slot),
+                        ItemView::default(),
+                    ))
+                    .with_children(|parent| spawn_item_view_parts(parent, font.clone()));
+            }
+        });
+}
+
+fn give_starting_items(
+    registry: Res<BlockRegistry>,
+    mut given: Local<bool>,
+    mut players: Query<&mut Inventory, With<LogicalPlayer>>,
+) {
+    // the registry holds only air until the block list is loaded
+    if *given || registry.iter().nth(1).is_none() {
+        return;
+    }
+    let Ok(mut inventory) = players.get_single_mut() else {
+        return;
+    };
+    for (name, count) in STARTING_ITEMS {
+        if let Some(item) = Item::from_name(name, &registry) {
+            inventory.add(ItemStack::new(item, count));
+        }
+    }
+    *given = true;
+}
+
+fn toggle_screen(
+    key: Res<Input<KeyCode>>,
+    console: Res<ConsoleState>,
+    mut screen: ResMut<InventoryScreen>,
+    mut windows: Query<&mut Window>,
+    mut players: Query<(&mut FpsController, &mut Inventory)>,
+) {
+    if console.is_open() || !key.just_pressed(KeyCode::E) {
+        return;
+    }
+    screen.open = !screen.open;
+    let open = screen.open;
+
+    let mut window = windows.single_mut();
+    window.cursor.visible = open;
+    window.cursor.grab_mode = if open { CursorGrabMode::None } else { CursorGrabMode::Locked };
+    for (mut controller, mut inventory) in &mut players {
+        controller.enable_input = !open;
+        if let Some((from, stack)) = screen.dragged.take() {
+            inventory.drop_stack(from, from, stack);
+        }
+    }
+}
+
+fn click_slots(
+    mouse: Res<Input<MouseButton>>,
+    key: Res<Input<KeyCode>>,
+    mut screen: ResMut<InventoryScreen>,
+    mut slots: Query<(&InventorySlot, &Interaction, &mut BackgroundColor)>,
+    mut players: Query<&mut Inventory, With<LogicalPlayer>>,
+) {
+    if !screen.open {
+        return;
+    }
+    let Ok(mut inventory) = players.get_single_mut() else {
+        return;
+    };
+
+    let mut hovered = None;
+    for (slot, interaction, mut color) in &mut slots {
+        let is_hovered = *interaction != Interaction::None;
+        if is_hovered {
+            hovered = Some(slot.0);
+        }
+        let wanted = if is_hovered { HOVERED_SLOT_COLOR } else { SLOT_COLOR };
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+
+    if mouse.just_pressed(MouseButton::Left) {
+        if let Some(slot) = hovered {
+            if key.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+                inventory.quick_move(slot);
+            } else if let Some(stack) = inventory.slots[slot].take() {
+                screen.dragged = Some((slot, stack));
+            }
+        }
+    }
+    if mouse.just_released(MouseButton::Left) {
+        // released outside of the slots the stack goes back
+        if let Some((from, stack)) = screen.dragged.take() {
+            inventory.drop_stack(from, hovered.unwrap_or(from), stack);
+        }
+    }
+}
+
+fn update_screen(
+    screen: Res<InventoryScreen>,
+    players: Query<&Inventory, With<LogicalPlayer>>,
+    mut roots: Query<&mut Visibility, With<InventoryRoot>>,
+    mut slots: Query<(&InventorySlot, &mut ItemView), Without<DraggedItem>>,
+    mut dragged: Query<&mut ItemView, With<DraggedItem>>,
+) {
+    if screen.is_changed() {
+        for mut visibility in &mut roots {
+            *visibility = if screen.open { Visibility::Inherited } else { Visibility::Hidden };
+        }
+        for mut view in &mut dragged {
+            view.set_if_neq(ItemView(screen.dragged.map(|(_, stack)| stack)));
+        }
+    }
+    let Ok(inventory) = players.get_single() else {
+        return;
+    };
+    for (slot, mut view) in &mut slots {
+        view.set_if_neq(ItemView(inventory.slots[slot.0]));
+    }
+}
+
+fn drag_item(
+    screen: Res<InventoryScreen>,
+    windows: Query<&Window>,
+    mut dragged: Query<&mut Style, With<DraggedItem>>,
+) {
+    let Some(cursor) = windows.single().cursor_position() else {
+        return;
+    };
+    if screen.dragged.is_none() {
+        return;
+    }
+    for mut style in &mut dragged {
+        style.left = Val::Px(cursor.x - 24.0);
+        style.top = Val::Px(cursor.y - 24.0);
+    }
+}
+
+/// `/give <item> [count]` adds blocks or a tool like `iron_pickaxe` to the inventory.
+fn give_command(
+    mut commands: EventReader<ConsoleCommand>,
+    mut replies: EventWriter<ConsoleReply>,
+    registry: Res<BlockRegistry>,
+    mut players: Query<&mut Inventory, With<LogicalPlayer>>,
+) {
+    for command in commands.iter().filter(|command| command.name == "give") {
+        let Some(name) = command.args.first() else {
+            replies.send(ConsoleReply("Usage: /give <item> [count]".to_string()));
+            continue;
+        };
+        let Some(item) = Item::from_name(name, &registry) else {
+            replies.send(ConsoleReply(format!("Unknown item {name}")));
+            continue;
+        };
+        let count = match command.args.get(1).map(|count| count.parse::<u32>()) {
+            None => 1,
+            Some(Ok(count)) if count > 0 => count,
+            Some(_) => {
+                replies.send(ConsoleReply("The count must be a positive number".to_string()));
+                continue;
+            }
+        };
+        for mut inventory in &mut players {
+            // counts above the stack size fill several slots
+            let mut given = 0;
+            while given < count {
+                let stack = ItemStack::new(item, count - given);
+                let left = inventory.add(stack).map_or(0, |left| left.count);
+                given += stack.count - left;
+                if left > 0 {
+                    break;
+                }
+            }
+            let reply = if given == count {
+                format!("Gave {count} {name}")
+            } else {
+                format!("Gave {given} of {count} {name}, the inventory is full")
+            };
+            replies.send(ConsoleReply(reply));
+        }
+    }
+}
+
+/// `/gamemode <survival|creative>`
+fn gamemode_command(
+    mut commands: EventReader<ConsoleCommand>,
+    mut replies: EventWriter<ConsoleReply>,
+    mut game_mode: ResMut<GameMode>,
+) {
+    for command in commands.iter().filter(|command| command.name == "gamemode") {
+        *game_mode = match command.args.first().map(String::as_str) {
+            Some("survival") => GameMode::Survival,
+            Some("creative") => GameMode::Creative,
+            _ => {
+                replies.send(ConsoleReply("Usage: /gamemode <survival|creative>".to_string()));
+                continue;
+            }
+        };
+        replies.send(ConsoleReply(format!("Game mode set to {:?}", *game_mode)));
+    }
+}
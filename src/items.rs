@@ -0,0 +1,183 @@
+//! Items held in the inventory: blocks to build with and tools that dig their blocks faster.
+//! Blocks that declare a tool tier only drop an item when dug with a good enough tool.
+
+use crate::blocks::{BlockDefinition, BlockId, BlockRegistry};
+
+/// Digging a block without the tool it needs is this many times slower, like in Minecraft.
+const WRONG_TOOL_PENALTY: f32 = 10.0 / 3.0;
+/// Most blocks of the same kind a slot holds.
+const MAX_BLOCK_STACK: u32 = 64;
+
+#[derive(serde::Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolKind {
+    Pickaxe,
+    Shovel,
+    Axe,
+}
+
+impl ToolKind {
+    const ALL: [ToolKind; 3] = [ToolKind::Pickaxe, ToolKind::Shovel, ToolKind::Axe];
+
+    fn name(self) -> &'static str {
+        match self {
+            ToolKind::Pickaxe => "pickaxe",
+            ToolKind::Shovel => "shovel",
+            ToolKind::Axe => "axe",
+        }
+    }
+}
+
+/// Material of a tool, better tiers dig faster, last longer and can dig harder blocks.
+#[derive(serde::Deserialize, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolTier {
+    Wooden,
+    Stone,
+    Iron,
+    Diamond,
+}
+
+impl ToolTier {
+    const ALL: [ToolTier; 4] = [ToolTier::Wooden, ToolTier::Stone, ToolTier::Iron, ToolTier::Diamond];
+
+    fn name(self) -> &'static str {
+        match self {
+            ToolTier::Wooden => "wooden",
+            ToolTier::Stone => "stone",
+            ToolTier::Iron => "iron",
+            ToolTier::Diamond => "diamond",
+        }
+    }
+
+    /// Multiplier of the dig speed on the blocks of the tool.
+    fn speed(self) -> f32 {
+        match self {
+            ToolTier::Wooden => 2.0,
+            ToolTier::Stone => 4.0,
+            ToolTier::Iron => 6.0,
+            ToolTier::Diamond => 8.0,
+        }
+    }
+
+    /// Blocks a new tool can dig before it breaks.
+    pub fn durability(self) -> u32 {
+        match self {
+            ToolTier::Wooden => 59,
+            ToolTier::Stone => 131,
+            ToolTier::Iron => 250,
+            ToolTier::Diamond => 1561,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Tool {
+    pub kind: ToolKind,
+    pub tier: ToolTier,
+}
+
+impl Tool {
+    /// Tool names are the tier followed by the kind, e.g. `stone_pickaxe`.
+    pub fn from_name(name: &str) -> Option<Tool> {
+        ToolTier::ALL.into_iter()
+            .flat_map(|tier| ToolKind::ALL.map(|kind| Tool { kind, tier }))
+            .find(|tool| tool.name() == name)
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}_{}", self.tier.name(), self.kind.name())
+    }
+
+    /// Icon of the tool for the UI.
+    pub fn texture(&self) -> String {
+        format!("textures/items/{}.png", self.name())
+    }
+
+    /// Whether the block drops an item when dug with this tool, `None` being the hand.
+    pub fn drops(tool: Option<Tool>, block: &BlockDefinition) -> bool {
+        match block.tool_tier {
+            None => true,
+            Some(tier) => tool.is_some_and(|tool| block.tool == Some(tool.kind) && tool.tier >= tier),
+        }
+    }
+
+    /// Seconds to dig the block with this tool, `None` being the hand.
+    pub fn dig_seconds(tool: Option<Tool>, block: &BlockDefinition) -> f32 {
+        let speed = match tool {
+            Some(tool) if block.tool == Some(tool.kind) => tool.tier.speed(),
+            _ => 1.0,
+        };
+        let penalty = if Tool::drops(tool, block) { 1.0 } else { WRONG_TOOL_PENALTY };
+        block.hardness * penalty / speed
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Item {
+    Block(BlockId),
+    Tool(Tool),
+}
+
+impl Item {
+    /// Looks up a tool or a block by name, unknown names are `None`.
+    pub fn from_name(name: &str, registry: &BlockRegistry) -> Option<Item> {
+        if let Some(tool) = Tool::from_name(name) {
+            return Some(Item::Tool(tool));
+        }
+        registry.iter().position(|block| block.name == name && block.is_visible())
+            .map(|index| Item::Block(BlockId(index as u16)))
+    }
+
+    /// Most items of this kind a slot holds, tools don't stack.
+    pub fn max_stack(&self) -> u32 {
+        match self {
+            Item::Block(_) => MAX_BLOCK_STACK,
+            Item::Tool(_) => 1,
+        }
+    }
+}
+
+/// Items of the same kind in a slot.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct ItemStack {
+    pub item: Item,
+    pub count: u32,
+    /// Blocks a tool can still dig, unused for blocks.
+    pub durability: u32,
+}
+
+impl ItemStack {
+    /// A stack of new items, capped to the stack size of the item.
+    pub fn new(item: Item, count: u32) -> Self {
+        let durability = match item {
+            Item::Tool(tool) => tool.tier.durability(),
+            Item::Block(_) => 0,
+        };
+        Self { item, count: count.min(item.max_stack()), durability }
+    }
+
+    pub fn tool(&self) -> Option<Tool> {
+        match self.item {
+            Item::Tool(tool) => Some(tool),
+            Item::Block(_) => None,
+        }
+    }
+
+    /// Durability left from 0 to 1, `None` for items that don't wear out.
+    pub fn wear(&self) -> Option<f32> {
+        self.tool().map(|tool| self.durability as f32 / tool.tier.durability() as f32)
+    }
+
+    /// Moves as many items as fit from `other` onto this stack, returns what is left of it.
+    pub fn merge(&mut self, other: ItemStack) -> Option<ItemStack> {
+        if other.item != self.item {
+            return Some(other);
+        }
+        let moved = other.count.min(self.item.max_stack().saturating_sub(self.count));
+        self.count += moved;
+        let left = other.count - moved;
+        (left > 0).then_some(ItemStack { count: left, ..other })
+    }
+}
+
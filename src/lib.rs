@@ -0,0 +1,8 @@
+//! Block data shared by the game and its benchmarks: block definitions and models, items,
+//! biome tints and chunk colliders.
+
+pub mod biomes;
+pub mod blocks;
+pub mod collision;
+pub mod items;
+pub mod models;
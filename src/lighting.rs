@@ -0,0 +1,259 @@
+//! Voxel light: sky light flooding down from the top of the world and block light spreading
+//! from emitters. Light is stored per chunk interior and baked into vertex colors when meshing.
+
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::blocks::{BlockId, BlockRegistry};
+use crate::terrain::CHUNK_SIZE;
+
+pub const MAX_LIGHT: u8 = 15;
+
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+const DIRECTIONS: [IVec3; 6] = [IVec3::X, IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y, IVec3::Z, IVec3::NEG_Z];
+
+/// Sky light is kept in the high nibble of a light value, block light in the low one.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Channel {
+    Sky,
+    Block,
+}
+
+impl Channel {
+    fn get(self, light: u8) -> u8 {
+        match self {
+            Channel::Sky => light >> 4,
+            Channel::Block => light & 0x0f,
+        }
+    }
+
+    fn with(self, light: u8, level: u8) -> u8 {
+        match self {
+            Channel::Sky => (light & 0x0f) | (level << 4),
+            Channel::Block => (light & 0xf0) | level,
+        }
+    }
+
+    /// Level a neighbour gets from a voxel at `level`, sky light goes straight down undimmed.
+    fn spread(self, level: u8, direction: IVec3) -> u8 {
+        if self == Channel::Sky && level == MAX_LIGHT && direction == IVec3::NEG_Y {
+            MAX_LIGHT
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+}
+
+/// Chunk and interior index of a world voxel.
+fn chunk_index(voxel: IVec3) -> ([i32; 3], usize) {
+    let chunk = (voxel - 1).div_euclid(IVec3::splat(CHUNK_SIZE));
+    let local = voxel - 1 - chunk * CHUNK_SIZE;
+    (chunk.to_array(), (local.x + CHUNK_SIZE * (local.y + CHUNK_SIZE * local.z)) as usize)
+}
+
+/// Light of every voxel in the loaded chunks, by world voxel position.
+#[derive(Resource, Default)]
+pub struct LightMap {
+    chunks: HashMap<[i32; 3], Vec<u8>>,
+}
+
+impl LightMap {
+    fn get(&self, voxel: IVec3) -> Option<u8> {
+        let (chunk, index) = chunk_index(voxel);
+        self.chunks.get(&chunk).map(|light| light[index])
+    }
+
+    fn set(&mut self, voxel: IVec3, light: u8) {
+        let (chunk, index) = chunk_index(voxel);
+        if let Some(chunk) = self.chunks.get_mut(&chunk) {
+            chunk[index] = light;
+        }
+    }
+
+    /// Vertex color multiplier of a face looking into `voxel`, outside the world is open sky.
+    pub fn brightness(&self, voxel: IVec3) -> f32 {
+        let light = self.get(voxel).unwrap_or(MAX_LIGHT << 4);
+        let level = Channel::Sky.get(light).max(Channel::Block.get(light)) as f32 / MAX_LIGHT as f32;
+        // same falloff as Minecraft's light table with a little ambient light in the dark
+        0.03 + 0.97 * level / (4.0 - 3.0 * level)
+    }
+
+    /// Lights a freshly generated world. `emitters` are the voxels of light emitting blocks.
+    pub fn generate(
+        chunks: impl Iterator<Item = [i32; 3]>,
+        emitters: impl Iterator<Item = IVec3>,
+        blocks: impl Fn(IVec3) -> Option<BlockId>,
+        registry: &BlockRegistry,
+    ) -> Self {
+        let mut light_map = LightMap { chunks: chunks.map(|chunk| (chunk, vec![0; CHUNK_VOLUME])).collect() };
+        let Some(min) = light_map.chunks.keys().copied().reduce(|a, b| IVec3::from(a).min(b.into()).to_array()) else {
+            return light_map;
+        };
+        let max = light_map.chunks.keys().copied().reduce(|a, b| IVec3::from(a).max(b.into()).to_array()).unwrap();
+        let min = IVec3::from(min) * CHUNK_SIZE + 1;
+        let max = IVec3::from(max) * CHUNK_SIZE + CHUNK_SIZE;
+        let blocks_light = |voxel: IVec3| blocks(voxel).map_or(true, |block| registry.get(block).blocks_light());
+
+        // sky columns down to the first block stopping light, `lowest` is the lowest lit voxel
+        let size_x = (max.x - min.x + 1) as usize;
+        let mut lowest = vec![max.y + 1; size_x * (max.z - min.z + 1) as usize];
+        for z in min.z..=max.z {
+            for x in min.x..=max.x {
+                let column = (x - min.x) as usize + (z - min.z) as usize * size_x;
+                let mut y = max.y;
+                while y >= min.y && !blocks_light(IVec3::new(x, y, z)) {
+                    light_map.set(IVec3::new(x, y, z), Channel::Sky.with(0, MAX_LIGHT));
+                    lowest[column] = y;
+                    y -= 1;
+                }
+            }
+        }
+
+        // only lit voxels next to shaded ones of a neighbour column have to spread sideways
+        let mut sky_queue = VecDeque::new();
+        for z in min.z..=max.z {
+            for x in min.x..=max.x {
+                let column_lowest = lowest[(x - min.x) as usize + (z - min.z) as usize * size_x];
+                for direction in [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z] {
+                    let neighbour = IVec3::new(x, 0, z) + direction;
+                    if neighbour.x < min.x || neighbour.x > max.x || neighbour.z < min.z || neighbour.z > max.z {
+                        continue;
+                    }
+                    let neighbour_lowest = lowest[(neighbour.x - min.x) as usize + (neighbour.z - min.z) as usize * size_x];
+                    for y in column_lowest..neighbour_lowest {
+                        sky_queue.push_back(IVec3::new(x, y, z));
+                    }
+                }
+            }
+        }
+
+        let mut block_queue = VecDeque::new();
+        for voxel in emitters {
+            if let Some(block) = blocks(voxel) {
+                light_map.set(voxel, Channel::Block.with(0, registry.get(block).light_emission.min(MAX_LIGHT)));
+                block_queue.push_back(voxel);
+            }
+        }
+
+        let mut changed = Vec::new();
+        light_map.propagate(Channel::Sky, sky_queue, &blocks_light, &mut changed);
+        light_map.propagate(Channel::Block, block_queue, &blocks_light, &mut changed);
+        light_map
+    }
+
+    /// Relights the world around a voxel whose block changed, returns every voxel whose light changed.
+    pub fn update(&mut self, voxel: IVec3, blocks: impl Fn(IVec3) -> Option<BlockId>, registry: &BlockRegistry) -> Vec<IVec3> {
+        let mut changed = vec![voxel];
+        let Some(block) = blocks(voxel) else {
+            return changed;
+        };
+        let block = registry.get(block);
+        let blocks_light = |voxel: IVec3| blocks(voxel).map_or(true, |block| registry.get(block).blocks_light());
+
+        for channel in [Channel::Sky, Channel::Block] {
+            let mut relight = self.remove(channel, voxel, &blocks, registry, &mut changed);
+
+            if channel == Channel::Block && block.light_emission > 0 {
+                let light = self.get(voxel).unwrap_or(0);
+                self.set(voxel, channel.with(light, block.light_emission.min(MAX_LIGHT)));
+                relight.push_back(voxel);
+            }
+            if !block.blocks_light() {
+                // the top of the world is open sky
+                if channel == Channel::Sky && self.get(voxel + IVec3::Y).is_none() {
+                    let light = self.get(voxel).unwrap_or(0);
+                    self.set(voxel, channel.with(light, MAX_LIGHT));
+                    relight.push_back(voxel);
+                }
+                relight.extend(DIRECTIONS.iter()
+                    .map(|direction| voxel + *direction)
+                    .filter(|neighbour| self.get(*neighbour).is_some_and(|light| channel.get(light) > 0)));
+            }
+
+            self.propagate(channel, relight, &blocks_light, &mut changed);
+        }
+        changed
+    }
+
+    /// Darkens the voxels lit through `voxel`, returns the voxels lit from elsewhere
+    /// that have to spread their light into the darkened area again.
+    fn remove(
+        &mut self,
+        channel: Channel,
+        voxel: IVec3,
+        blocks: &impl Fn(IVec3) -> Option<BlockId>,
+        registry: &BlockRegistry,
+        changed: &mut Vec<IVec3>,
+    ) -> VecDeque<IVec3> {
+        let mut relight = VecDeque::new();
+        let Some(light) = self.get(voxel) else {
+            return relight;
+        };
+        self.set(voxel, channel.with(light, 0));
+
+        let mut queue = VecDeque::from([(voxel, channel.get(light))]);
+        while let Some((voxel, level)) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let neighbour = voxel + direction;
+                let Some(light) = self.get(neighbour) else {
+                    continue;
+                };
+                let neighbour_level = channel.get(light);
+                if neighbour_level == 0 {
+                    continue;
+                }
+
+                let spread = channel.spread(level, direction);
+                if (neighbour_level <= spread && neighbour_level < MAX_LIGHT)
+                    || (neighbour_level == MAX_LIGHT && spread == MAX_LIGHT)
+                {
+                    self.set(neighbour, channel.with(light, 0));
+                    changed.push(neighbour);
+                    queue.push_back((neighbour, neighbour_level));
+
+                    // emitters in the darkened area shine again
+                    let emission = blocks(neighbour).map_or(0, |block| registry.get(block).light_emission.min(MAX_LIGHT));
+                    if channel == Channel::Block && emission > 0 {
+                        self.set(neighbour, channel.with(light, emission));
+                        relight.push_back(neighbour);
+                    }
+                } else {
+                    relight.push_back(neighbour);
+                }
+            }
+        }
+        relight
+    }
+
+    fn propagate(
+        &mut self,
+        channel: Channel,
+        mut queue: VecDeque<IVec3>,
+        blocks_light: &impl Fn(IVec3) -> bool,
+        changed: &mut Vec<IVec3>,
+    ) {
+        while let Some(voxel) = queue.pop_front() {
+            let Some(light) = self.get(voxel) else {
+                continue;
+            };
+            let level = channel.get(light);
+            for direction in DIRECTIONS {
+                let spread = channel.spread(level, direction);
+                if spread == 0 {
+                    continue;
+                }
+                let neighbour = voxel + direction;
+                let Some(neighbour_light) = self.get(neighbour) else {
+                    continue;
+                };
+                if channel.get(neighbour_light) < spread && !blocks_light(neighbour) {
+                    self.set(neighbour, channel.with(neighbour_light, spread));
+                    changed.push(neighbour);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+}
@@ -4,7 +4,21 @@ use std::f32::consts::TAU;
 mod terrain;
 mod skybox;
 mod ui;
-
+mod atlas;
+mod lighting;
+mod console;
+mod world_time;
+mod weather;
+mod clouds;
+mod fog;
+mod water;
+mod breaking;
+mod inventory;
+mod drops;
+
+use minecraft_bevy_rust::{biomes, blocks, collision, items, models};
+
+use bevy::core_pipeline::bloom::BloomSettings;
 use bevy::core_pipeline::experimental::taa::{TemporalAntiAliasPlugin, TemporalAntiAliasBundle};
 use bevy_fps_controller::controller::*;
 use bevy::pbr::{NotShadowCaster, NotShadowReceiver};
@@ -13,9 +27,19 @@ use bevy::{prelude::*, window::CursorGrabMode};
 
 use bevy_rapier3d::prelude::*;
 
+use crate::blocks::BlockId;
+use crate::breaking::{BreakingPlugin, TargetBlock};
+use crate::clouds::CloudsPlugin;
+use crate::console::{console_input, ConsolePlugin, ConsoleState};
+use crate::drops::{DropsPlugin, ITEM_GROUP};
+use crate::fog::FogPlugin;
+use crate::inventory::{GameMode, Inventory, InventoryPlugin, InventoryScreen};
 use crate::skybox::SkyboxPlugin;
-use crate::terrain::WorldPlugin;
+use crate::terrain::{VoxelWorld, WorldPlugin};
 use crate::ui::MyUiPlugin;
+use crate::water::WaterPlugin;
+use crate::weather::WeatherPlugin;
+use crate::world_time::WorldTimePlugin;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default, States)]
 pub enum GameState {
@@ -43,6 +67,8 @@ pub fn main() {
         )
         .add_state::<GameState>()
         .add_plugins((SkyboxPlugin, TemporalAntiAliasPlugin))
+        .add_plugins((ConsolePlugin, WorldTimePlugin, WeatherPlugin, CloudsPlugin, FogPlugin, WaterPlugin))
+        .add_plugins((BreakingPlugin, InventoryPlugin, DropsPlugin))
         .add_plugins((WorldPlugin, MyUiPlugin, FpsControllerPlugin))
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
         // .add_plugin(RapierDebugRenderPlugin::default())
@@ -56,7 +82,10 @@ pub fn main() {
             )
                 .run_if(in_state(GameState::InGame)),
         )
-        .add_systems(Update, bevy::window::close_on_esc)
+        // Escape closes the console first
+        .add_systems(Update, bevy::window::close_on_esc
+            .run_if(|console: Res<ConsoleState>| !console.is_open())
+            .before(console_input))
         .run();
 }
 
@@ -66,6 +95,7 @@ fn setup(mut commands: Commands,
          mut materials: ResMut<Assets<StandardMaterial>>) {
     commands.spawn((
         Collider::capsule(Vec3::Y * 0.5, Vec3::Y * 1.5, 0.45),
+        CollisionGroups::new(Group::GROUP_1, !ITEM_GROUP),
         ActiveEvents::COLLISION_EVENTS,
         Velocity::zero(),
         RigidBody::Dynamic,
@@ -76,6 +106,7 @@ fn setup(mut commands: Commands,
         Ccd { enabled: true }, // Prevent clipping when going fast
         SpatialBundle::from_transform(Transform::from_xyz(0.0, 25.0, 0.0)),
         LogicalPlayer(0),
+        Inventory::default(),
         FpsControllerInput {
             pitch: -TAU / 12.0,
             yaw: TAU * 5.0 / 8.0,
@@ -90,21 +121,7 @@ fn setup(mut commands: Commands,
             sensitivity: 0.002,
             ..default()
         }
-    )).with_children(|builder|
-        {
-            builder.spawn(PointLightBundle {
-
-                // transform: Transform::from_xyz(5.0, 8.0, 2.0),
-                transform: Transform::from_xyz(0.0, 1.5, 0.0),
-                point_light: PointLight {
-                    intensity: 100.0, // lumens - roughly a 100W non-halogen incandescent bulb
-                    color: Color::ANTIQUE_WHITE,
-                    shadows_enabled: true,
-                    ..default()
-                },
-                ..default()
-            });
-        });
+    ));
 
     commands.spawn((
         Camera3dBundle {
@@ -123,6 +140,7 @@ fn setup(mut commands: Commands,
             ..default()
         },
         TemporalAntiAliasBundle::default(),
+        BloomSettings::default(),
         RenderPlayer(0),
     ));
 
@@ -228,7 +246,7 @@ fn update_system(
 }
 
 enum DigEventType {
-    Build,
+    Build(BlockId),
     Dig,
 }
 
@@ -240,45 +258,43 @@ struct DigEvent {
 
 const DIG_DISTANCE: Real = 4.0;
 
+#[allow(clippy::too_many_arguments)]
 fn cast_ray(rapier_context: Res<RapierContext>,
-            controllers: Query<(&Transform, &Collider, &FpsController), Without<OutlineCube>>,
+            voxel_world: VoxelWorld,
+            game_mode: Res<GameMode>,
+            screen: Res<InventoryScreen>,
+            console: Res<ConsoleState>,
+            mut target: ResMut<TargetBlock>,
+            mut controllers: Query<(&Transform, &Collider, &FpsController, &mut Inventory), Without<OutlineCube>>,
             mut outline_cube: Query<(&mut Transform, &mut Visibility), With<OutlineCube>>,
             btn: Res<Input<MouseButton>>,
             mut ev: EventWriter<DigEvent>) {
 
     let mut outline_cube = outline_cube.single_mut();
- 
-    for (transform, collider, controller) in controllers.iter() {
+    target.0 = None;
+
+    for (transform, collider, controller, mut inventory) in controllers.iter_mut() {
         if let Some(capsule) = collider.as_capsule() {
             let camera_height = capsule.segment().b().y + capsule.radius() * 0.75;
             let ray_pos = transform.translation + Vec3::Y * camera_height;
             let quat = Quat::from_euler(EulerRot::ZYX, 0.0, controller.yaw, controller.pitch);
             let ray_dir = -quat.mul_vec3(Vec3::Z);
-            let max_toi = DIG_DISTANCE;
-            let solid = false;
-            let filter = QueryFilter { flags: QueryFilterFlags::ONLY_FIXED, ..default() };
-
-
-            if let Some((_entity, toi)) = rapier_context.cast_ray(
-                ray_pos, ray_dir, max_toi, solid, filter,
-            ) {
 
-                // TODO: use normal to identify which side of cube build
-                let inside_hit_point = ray_pos + ray_dir * (toi * 1.1);
-
-                let position = inside_hit_point.floor() + Vec3::new(0.5, 0.5, 0.5);
+            // voxels are traced instead of colliders, plants have no collision but can be dug
+            if let Some((voxel, normal)) = voxel_world.raycast(ray_pos, ray_dir, DIG_DISTANCE) {
+                let position = voxel.as_vec3() + Vec3::new(0.5, 0.5, 0.5);
 
                 outline_cube.0.translation = position;
                 *outline_cube.1 = Visibility::Visible;
+                target.0 = Some(voxel);
 
-                if btn.just_pressed(MouseButton::Left) {
-                    ev.send(DigEvent { event_type: DigEventType::Dig, world_position: inside_hit_point });
-                } else if btn.just_pressed(MouseButton::Right) {
+                // digging takes time, see breaking.rs
+                if btn.just_pressed(MouseButton::Right) && !screen.is_open() && !console.is_open() {
                     let shape = Collider::cuboid(0.5, 0.5, 0.5);
-                    let outside_hit_point = ray_pos + ray_dir * (toi * 0.9);
-                    let shape_pos = outside_hit_point.floor() + Vec3::new(0.5, 0.5, 0.5);
+                    let shape_pos = position + normal.as_vec3();
                     let shape_rot = Quat::IDENTITY;
-                    let filter = QueryFilter::only_dynamic();
+                    // dropped items don't stop building
+                    let filter = QueryFilter::only_dynamic().groups(CollisionGroups::new(Group::ALL, !ITEM_GROUP));
 
                     let mut allow = true;
                     rapier_context.intersections_with_shape(
@@ -286,8 +302,11 @@ fn cast_ray(rapier_context: Res<RapierContext>,
                             allow = false;
                             true
                         });
-                    if allow {
-                        ev.send(DigEvent { event_type: DigEventType::Build, world_position: outside_hit_point });
+                    if let (true, Some(block)) = (allow, inventory.selected_block()) {
+                        ev.send(DigEvent { event_type: DigEventType::Build(block), world_position: shape_pos });
+                        if *game_mode == GameMode::Survival {
+                            inventory.take_selected();
+                        }
                     }
                 }
             } else {
@@ -301,8 +320,13 @@ pub fn manage_cursor(
     mut windows: Query<&mut Window>,
     btn: Res<Input<MouseButton>>,
     key: Res<Input<KeyCode>>,
+    screen: Res<InventoryScreen>,
     mut controllers: Query<&mut FpsController>,
 ) {
+    // the inventory screen frees the cursor to move items around
+    if screen.is_open() {
+        return;
+    }
     let mut window = windows.single_mut();
     if btn.just_pressed(MouseButton::Left) {
         window.cursor.visible = false;
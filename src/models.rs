@@ -0,0 +1,139 @@
+//! Block shapes other than full cubes. Positions are in block space where a block spans 0..1.
+
+use bevy::prelude::*;
+
+/// Shape a block is meshed and collided with. Full cubes are meshed by block-mesh,
+/// every other model is emitted quad by quad from [`BlockModel::quads`].
+#[derive(serde::Deserialize, Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockModel {
+    #[default]
+    Cube,
+    /// Lower half of a block.
+    Slab,
+    /// Slab with a step on its +Z half, stairs can't be rotated yet.
+    Stairs,
+    /// Two crossed double-sided quads for plants, without collision.
+    Cross,
+    /// Thin post, it collides 1.5 blocks high so it can't be jumped over.
+    FencePost,
+    /// Standing torch stick, without collision.
+    Torch,
+}
+
+const POST_MIN: f32 = 6.0 / 16.0;
+const POST_MAX: f32 = 10.0 / 16.0;
+
+const CUBE_BOXES: &[(Vec3, Vec3)] = &[(Vec3::ZERO, Vec3::ONE)];
+const SLAB_BOXES: &[(Vec3, Vec3)] = &[(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0))];
+const STAIRS_BOXES: &[(Vec3, Vec3)] = &[
+    (Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0)),
+    (Vec3::new(0.0, 0.5, 0.5), Vec3::ONE),
+];
+const POST_BOXES: &[(Vec3, Vec3)] = &[(Vec3::new(POST_MIN, 0.0, POST_MIN), Vec3::new(POST_MAX, 1.0, POST_MAX))];
+const POST_COLLISION_BOXES: &[(Vec3, Vec3)] = &[(Vec3::new(POST_MIN, 0.0, POST_MIN), Vec3::new(POST_MAX, 1.5, POST_MAX))];
+const TORCH_BOXES: &[(Vec3, Vec3)] = &[(Vec3::new(7.0 / 16.0, 0.0, 7.0 / 16.0), Vec3::new(9.0 / 16.0, 10.0 / 16.0, 9.0 / 16.0))];
+
+/// Faces of a box as (normal, right, up) seen from outside, right × up = normal.
+const BOX_FACES: [[Vec3; 3]; 6] = [
+    [Vec3::X, Vec3::NEG_Z, Vec3::Y],
+    [Vec3::NEG_X, Vec3::Z, Vec3::Y],
+    [Vec3::Y, Vec3::X, Vec3::NEG_Z],
+    [Vec3::NEG_Y, Vec3::X, Vec3::Z],
+    [Vec3::Z, Vec3::X, Vec3::Y],
+    [Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y],
+];
+
+/// Counter clockwise triangles of a quad given as bottom left, bottom right, top left, top right.
+pub const QUAD_INDICES: [u32; 6] = [0, 1, 3, 0, 3, 2];
+
+/// Inset of plant quads from the block corners.
+const CROSS_INSET: f32 = 0.15;
+
+pub struct ModelQuad {
+    pub positions: [Vec3; 4],
+    pub normal: Vec3,
+    /// Texture coordinates across the block face, v points up.
+    pub tex_coords: [Vec2; 4],
+    /// Quads lying on the block boundary are hidden by an opaque neighbour.
+    pub on_boundary: bool,
+}
+
+impl BlockModel {
+    /// Boxes the model is rendered with, crosses are quads only.
+    pub fn boxes(self) -> &'static [(Vec3, Vec3)] {
+        match self {
+            BlockModel::Cube => CUBE_BOXES,
+            BlockModel::Slab => SLAB_BOXES,
+            BlockModel::Stairs => STAIRS_BOXES,
+            BlockModel::Cross => &[],
+            BlockModel::FencePost => POST_BOXES,
+            BlockModel::Torch => TORCH_BOXES,
+        }
+    }
+
+    pub fn collision_boxes(self) -> &'static [(Vec3, Vec3)] {
+        match self {
+            BlockModel::Cross | BlockModel::Torch => &[],
+            BlockModel::FencePost => POST_COLLISION_BOXES,
+            model => model.boxes(),
+        }
+    }
+
+    pub fn quads(self) -> Vec<ModelQuad> {
+        match self {
+            BlockModel::Cross => cross_quads(),
+            model => model.boxes().iter()
+                .flat_map(|(min, max)| BOX_FACES.map(|face| box_face(*min, *max, face)))
+                .collect(),
+        }
+    }
+}
+
+fn box_face(min: Vec3, max: Vec3, [normal, right, up]: [Vec3; 3]) -> ModelQuad {
+    let center = (min + max) / 2.0;
+    let half = (max - min) / 2.0;
+    let positions = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+        .map(|(r, u)| center + normal * half + right * half * r + up * half * u);
+    let plane = (center + normal * half).dot(normal);
+    ModelQuad {
+        positions,
+        normal,
+        tex_coords: positions.map(|position| Vec2::new(axis_fraction(position, right), axis_fraction(position, up))),
+        on_boundary: plane == 0.0 || plane == 1.0,
+    }
+}
+
+/// Position along an axis of the block, measured from the side the axis points away from.
+fn axis_fraction(position: Vec3, axis: Vec3) -> f32 {
+    let coordinate = position.dot(axis);
+    if axis.min_element() < 0.0 {
+        1.0 + coordinate
+    } else {
+        coordinate
+    }
+}
+
+fn cross_quads() -> Vec<ModelQuad> {
+    let low = CROSS_INSET;
+    let high = 1.0 - CROSS_INSET;
+    let diagonals = [
+        (Vec3::new(low, 0.0, low), Vec3::new(high, 0.0, high)),
+        (Vec3::new(low, 0.0, high), Vec3::new(high, 0.0, low)),
+    ];
+    let tex_coords = [Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0), Vec2::new(1.0, 1.0)];
+
+    let mut quads = Vec::with_capacity(4);
+    for (left, right) in diagonals {
+        // both sides are emitted, plants are lit as if facing up to avoid dark back sides
+        for (left, right) in [(left, right), (right, left)] {
+            quads.push(ModelQuad {
+                positions: [left, right, left + Vec3::Y, right + Vec3::Y],
+                normal: Vec3::Y,
+                tex_coords,
+                on_boundary: false,
+            });
+        }
+    }
+    quads
+}
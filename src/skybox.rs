@@ -1,111 +1,294 @@
-//! Load a cubemap texture onto a cube like a skybox and cycle through different compressed texture formats
+//! Sky cube around the camera, it blends from the day to the night cubemap with the world time
+//! and draws the sun and the moon. `/skybox` switches between the cubemaps of [`SKYBOXES`].
 
 use bevy::{
     asset::LoadState,
-    core_pipeline::Skybox,
+    pbr::{MaterialPipeline, MaterialPipelineKey, NotShadowCaster, NotShadowReceiver},
     prelude::*,
-    render::texture::CompressedImageFormats,
-    
+    reflect::{TypePath, TypeUuid},
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_resource::{
+            AsBindGroup, Extent3d, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+            TextureViewDescriptor, TextureViewDimension,
+        },
+        renderer::RenderDevice,
+        texture::CompressedImageFormats,
+    },
 };
 
-const CUBEMAPS: &[(&str, CompressedImageFormats)] = &[
-    (
-        "textures/clear_sky.ktx2",
-        CompressedImageFormats::BC,
-    )
+use crate::console::{ConsoleCommand, ConsoleReply};
+use crate::fog::SkyFog;
+use crate::weather::Weather;
+use crate::world_time::WorldTime;
+
+/// Sky cubemaps, picked per world or dimension. Each lists versions of its day sky from the
+/// preferred one down, the first one in a format the GPU can decode is loaded. PNG skies are
+/// faces stacked vertically in the order +X, -X, +Y, -Y, +Z, -Z or a horizontal cross.
+const SKYBOXES: &[SkyboxSet] = &[
+    SkyboxSet {
+        name: "overworld",
+        day: &[
+            ("textures/clear_sky.ktx2", CompressedImageFormats::BC),
+            ("textures/clear_sky.png", CompressedImageFormats::NONE),
+        ],
+        night: "textures/night_sky.png",
+    },
+    SkyboxSet {
+        name: "starry",
+        day: &[("textures/night_sky.png", CompressedImageFormats::NONE)],
+        night: "textures/night_sky.png",
+    },
 ];
 
+struct SkyboxSet {
+    name: &'static str,
+    day: &'static [(&'static str, CompressedImageFormats)],
+    night: &'static str,
+}
+
+/// Half size of the sky cube, it has to stay inside the camera's far plane.
+const SKY_SIZE: f32 = 400.0;
+
 pub struct SkyboxPlugin;
 
 impl Plugin for SkyboxPlugin {
     fn build(&self, app: &mut App) {
         app
+        .add_plugins(MaterialPlugin::<SkyMaterial>::default())
         .add_systems(Startup, setup)
         .add_systems(
             Update,
-            asset_loaded,
+            ((skybox_command, asset_loaded).chain(), follow_camera, update_sky),
         );
     }
 }
 
+#[derive(AsBindGroup, TypeUuid, TypePath, Clone)]
+#[uuid = "5f2d1c7e-8a43-4b6e-9d21-3c6f0a9b7e14"]
+pub struct SkyMaterial {
+    #[uniform(0)]
+    sun_direction: Vec3,
+    #[uniform(0)]
+    night_blend: f32,
+    #[uniform(0)]
+    moon_direction: Vec3,
+    #[uniform(0)]
+    twilight: f32,
+    #[uniform(0)]
+    overcast: f32,
+    /// The sky fades into the fog at the horizon, or everywhere with an alpha of 1.
+    #[uniform(0)]
+    fog_color: Color,
+    #[texture(1, dimension = "cube")]
+    #[sampler(2)]
+    day: Handle<Image>,
+    #[texture(3, dimension = "cube")]
+    #[sampler(4)]
+    night: Handle<Image>,
+}
+
+impl Material for SkyMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/sky.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // the camera is inside the cube
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+
 #[derive(Resource)]
-struct Cubemap {
+pub struct Cubemap {
     is_loaded: bool,
+    /// Index into [`SKYBOXES`].
     index: usize,
+    formats: CompressedImageFormats,
     image_handle: Handle<Image>,
+    night_handle: Handle<Image>,
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let skybox_handle = asset_server.load(CUBEMAPS[0].0);
+impl Cubemap {
+    /// The day sky once it's loaded and turned into a cube.
+    pub fn day(&self) -> Option<&Handle<Image>> {
+        self.is_loaded.then_some(&self.image_handle)
+    }
 
-    // directional 'sun' light
-    commands.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            illuminance: 12000.0,
-            shadows_enabled: true,
-            ..default()
-        },
-        // TODO: why with cascade_shadow_config, shadows disappear?
-        // cascade_shadow_config: bevy::pbr::CascadeShadowConfigBuilder {
-        //     maximum_distance: 3.0,
-        //     first_cascade_far_bound: 0.9,
-        //     ..default()
-        // }.into(),
-        transform: Transform {
-            translation: Vec3::new(0.0, 2.0, 0.0),
-            rotation: Quat::from_euler(
-                EulerRot::XYZ,
-                (-45.0_f32).to_radians(),
-                (25.0_f32).to_radians(),
-                (0.0_f32).to_radians(),
-            ),
-            ..default()
-        },
-        ..default()
-    });
-    
-
-    // ambient light
-    // NOTE: The ambient light is used to scale how bright the environment map is so with a bright
-    // environment map, use an appropriate color and brightness to match
-    commands.insert_resource(AmbientLight {
-        color: Color::rgb_u8(210, 220, 240),
-        brightness: 0.5,
-    });
+    fn load(&mut self, index: usize, asset_server: &AssetServer) {
+        let skybox = &SKYBOXES[index];
+        let (path, _) = skybox.day.iter()
+            .find(|(_, formats)| self.formats.contains(*formats))
+            .unwrap_or(skybox.day.last().unwrap());
+        info!("Loading {path} for the {} sky", skybox.name);
+
+        self.is_loaded = false;
+        self.index = index;
+        self.image_handle = asset_server.load(*path);
+        self.night_handle = asset_server.load(skybox.night);
+    }
+}
+
+#[derive(Component)]
+struct Sky;
 
-    commands.insert_resource(Cubemap {
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, render_device: Res<RenderDevice>) {
+    let mut cubemap = Cubemap {
         is_loaded: false,
         index: 0,
-        image_handle: skybox_handle,
+        formats: CompressedImageFormats::from_features(render_device.features()),
+        image_handle: Handle::default(),
+        night_handle: Handle::default(),
+    };
+    cubemap.load(0, &asset_server);
+    commands.insert_resource(cubemap);
+}
+
+/// Turns a 2d image into a cube, the faces of a horizontal cross are stacked first.
+fn prepare_cubemap(image: &mut Image) {
+    // ktx2 cubemaps already have their layers
+    if image.texture_descriptor.array_layer_count() != 1 {
+        return;
+    }
+    if image.width() * 3 == image.height() * 4 {
+        cross_to_stack(image);
+    }
+    image.reinterpret_stacked_2d_as_array(image.height() / image.width());
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..default()
     });
+}
+
+fn cross_to_stack(image: &mut Image) {
+    let face = image.width() as usize / 4;
+    let pixel_bytes = image.data.len() / (image.width() * image.height()) as usize;
+    let row_bytes = image.width() as usize * pixel_bytes;
+    let face_row_bytes = face * pixel_bytes;
 
+    // column and row of +X, -X, +Y, -Y, +Z, -Z in the cross
+    let faces = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+    let mut data = Vec::with_capacity(face_row_bytes * face * faces.len());
+    for (column, row) in faces {
+        for y in 0..face {
+            let start = (row * face + y) * row_bytes + column * face_row_bytes;
+            data.extend_from_slice(&image.data[start..start + face_row_bytes]);
+        }
+    }
+    image.data = data;
+    image.texture_descriptor.size = Extent3d {
+        width: face as u32,
+        height: (face * faces.len()) as u32,
+        depth_or_array_layers: 1,
+    };
 }
 
 fn asset_loaded(
     asset_server: Res<AssetServer>,
-    _images: ResMut<Assets<Image>>,
+    mut images: ResMut<Assets<Image>>,
     mut cubemap: ResMut<Cubemap>,
-    mut skyboxes: Query<&mut Skybox>,
-    mut camera: Query<
-        Entity,
-        With<Camera>
-    >,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+    sky: Query<&Handle<SkyMaterial>, With<Sky>>,
     mut commands: Commands,
 ) {
     if !cubemap.is_loaded
         && asset_server.get_load_state(cubemap.image_handle.clone_weak()) == LoadState::Loaded
+        && asset_server.get_load_state(cubemap.night_handle.clone_weak()) == LoadState::Loaded
     {
-        info!("Swapping to {}...", CUBEMAPS[cubemap.index].0);
+        info!("Swapping to the {} sky...", SKYBOXES[cubemap.index].name);
 
-        let camera_entity = camera.single_mut();
-        let mut camera = commands.entity(camera_entity);
-        camera.insert(Skybox(cubemap.image_handle.clone()));
+        for handle in [&cubemap.image_handle, &cubemap.night_handle] {
+            if let Some(image) = images.get_mut(handle) {
+                prepare_cubemap(image);
+            }
+        }
+        cubemap.is_loaded = true;
 
-        for mut skybox in &mut skyboxes {
-            skybox.0 = cubemap.image_handle.clone();
+        if let Ok(handle) = sky.get_single() {
+            if let Some(material) = materials.get_mut(handle) {
+                material.day = cubemap.image_handle.clone();
+                material.night = cubemap.night_handle.clone();
+            }
+            return;
         }
 
-        cubemap.is_loaded = true;
+        commands.spawn((
+            MaterialMeshBundle {
+                mesh: meshes.add(Mesh::from(shape::Cube { size: SKY_SIZE * 2.0 })),
+                material: materials.add(SkyMaterial {
+                    sun_direction: Vec3::Y,
+                    night_blend: 0.0,
+                    moon_direction: Vec3::NEG_Y,
+                    twilight: 0.0,
+                    overcast: 0.0,
+                    fog_color: Color::NONE,
+                    day: cubemap.image_handle.clone(),
+                    night: cubemap.night_handle.clone(),
+                }),
+                ..default()
+            },
+            NotShadowCaster,
+            NotShadowReceiver,
+            Sky,
+        ));
+    }
+}
 
+fn skybox_command(
+    mut commands: EventReader<ConsoleCommand>,
+    mut replies: EventWriter<ConsoleReply>,
+    asset_server: Res<AssetServer>,
+    mut cubemap: ResMut<Cubemap>,
+) {
+    for command in commands.iter().filter(|command| command.name == "skybox") {
+        let name = command.args.first().map(String::as_str).unwrap_or_default();
+        match SKYBOXES.iter().position(|skybox| skybox.name == name) {
+            Some(index) => {
+                cubemap.load(index, &asset_server);
+                replies.send(ConsoleReply(format!("Skybox set to {name}")));
+            }
+            None => {
+                let names: Vec<&str> = SKYBOXES.iter().map(|skybox| skybox.name).collect();
+                replies.send(ConsoleReply(format!("Usage: /skybox <{}>", names.join("|"))));
+            }
+        }
+    }
+}
+
+fn follow_camera(
+    camera: Query<&GlobalTransform, With<Camera3d>>,
+    mut sky: Query<&mut Transform, With<Sky>>,
+) {
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+    for mut transform in &mut sky {
+        transform.translation = camera.translation();
     }
-}
\ No newline at end of file
+}
+
+fn update_sky(
+    time: Res<WorldTime>,
+    weather: Res<Weather>,
+    fog: Res<SkyFog>,
+    sky: Query<&Handle<SkyMaterial>, With<Sky>>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+) {
+    for handle in &sky {
+        if let Some(material) = materials.get_mut(handle) {
+            material.sun_direction = time.sun_direction();
+            material.moon_direction = time.moon_direction();
+            material.night_blend = 1.0 - time.daylight();
+            material.twilight = time.twilight();
+            material.overcast = weather.overcast();
+            material.fog_color = fog.color.with_a(if fog.underwater { 1.0 } else { 0.0 });
+        }
+    }
+}
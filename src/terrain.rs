@@ -1,8 +1,12 @@
 use bevy::{
+    ecs::system::{EntityCommands, SystemParam},
+    pbr::NotShadowCaster,
     prelude::*,
     render::{
         mesh::Indices,
-        render_resource::PrimitiveTopology, view::NoFrustumCulling,
+        primitives::Aabb,
+        render_resource::PrimitiveTopology,
+        view::{VisibilitySystems, VisibleEntities},
     },
 };
 
@@ -10,7 +14,7 @@ use fast_poisson::Poisson2D;
 
 use noise::{Fbm, OpenSimplex, Worley};
 use bevy_rapier3d::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::SystemTime;
 use bevy::asset::LoadState;
 
@@ -19,24 +23,38 @@ use bevy::asset::LoadState;
 
 use noise::utils::{NoiseMapBuilder, PlaneMapBuilder};
 
-use block_mesh::ndshape::{ConstShape, ConstShape3u32};
-use block_mesh::{visible_block_faces, UnitQuadBuffer, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG, OrientedBlockFace};
+use block_mesh::ndshape::{ConstShape, ConstShape3u32, RuntimeShape, Shape};
+use block_mesh::{visible_block_faces, UnitQuad, UnitQuadBuffer, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG};
 
 use bevy_common_assets::json::JsonAssetPlugin;
 use rand::Rng;
+use bevy_fps_controller::controller::LogicalPlayer;
 use crate::{DigEvent, DigEventType};
+use crate::atlas::{BlockAtlas, BlockIcons, BlockTextureLoading, TextureAnimation};
+use crate::biomes::{BiomeTint, Colormap};
+use crate::collision::chunk_collider;
+use crate::drops::ItemMeshes;
+use crate::lighting::LightMap;
+use crate::blocks::{BlockDefinition, BlockId, BlockList, BlockRegistry, BlockVisibility};
+use crate::models::{BlockModel, QUAD_INDICES};
+use crate::water::WaterMaterial;
 
 
 const CHUNKS_COUNT_X: i32 = 32;
 const CHUNKS_COUNT_Y: i32 = 32;
 const CHUNKS_COUNT_Z: i32 = 32;
 
-const CHUNK_SIZE: i32 = 32;
+pub const CHUNK_SIZE: i32 = 32;
 
 
-const VISIBLE_CHUNK_DISTANCE: i32 = 1;
+/// Chunks generated around the spawn along each axis, the fog ends at this distance.
+pub const VISIBLE_CHUNK_DISTANCE: i32 = 3;
+/// Chunks this close to the spawn are meshed while the world is generated, the farther rings
+/// are left to [`remesh_dirty_chunks`] and its per frame budget.
+const MESHED_CHUNK_DISTANCE: i32 = 1;
 const CHUNKS_COUNT_DIM: i32 = VISIBLE_CHUNK_DISTANCE * 2 + 1;
 const BUILDER_HEIGHT_SCALE: f32 = 20.0;
+const SEA_LEVEL: i32 = CHUNKS_COUNT_Y * CHUNK_SIZE / 2 - 3;
 
 type SampleShape = ConstShape3u32<34, 34, 34>;
 
@@ -45,84 +63,139 @@ pub struct WorldPlugin;
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_plugins(JsonAssetPlugin::<TextureAtlas>::new(&["json"]))
+            .add_plugins(JsonAssetPlugin::<BlockList>::new(&["blocks.json"]))
+            .add_plugins(JsonAssetPlugin::<TextureAnimation>::new(&["anim.json"]))
+            .init_resource::<BlockRegistry>()
+            .init_resource::<ChunkMap>()
+            .init_resource::<LightMap>()
+            .init_resource::<ChunkRemeshSettings>()
+            .init_resource::<ChunkLodSettings>()
             .add_systems(Startup, load_atlas)
-            .add_systems(Update, (asset_loaded, dig_event_handler))
+            .add_systems(Update, (
+                asset_loaded,
+                animate_block_textures,
+                (dig_event_handler, update_chunk_lod, apply_deferred, remesh_dirty_chunks).chain(),
+            ))
+            .init_resource::<ChunkCullingStats>()
+            .add_systems(PostUpdate, update_culling_stats.after(VisibilitySystems::CheckVisibility))
         ;
     }
 }
 
-#[derive(serde::Deserialize, bevy::reflect::TypeUuid, bevy::reflect::TypePath)]
-#[uuid = "e2df0986-b464-4766-ad99-e0ff9cfd05a7"]
-struct TextureAtlas {
-    frames: HashMap<String, ImageDescription>,
-    meta: Meta,
-}
+#[derive(Component)]
+struct Cube;
 
+/// Chunk entities by chunk position, so edits can find every chunk that stores a voxel.
+#[derive(Resource, Default)]
+pub struct ChunkMap(HashMap<[i32; 3], Entity>);
 
-#[derive(serde::Deserialize, bevy::reflect::TypeUuid, bevy::reflect::TypePath)]
-#[uuid = "a86ea47c-3a79-46b8-b67c-289e9fbb55d0"]
-struct Meta {
-    size: Size,
-}
+/// Marks a chunk whose samples changed since its mesh and collider were last built.
+#[derive(Component)]
+struct DirtyChunk;
 
+#[derive(Resource)]
+pub struct ChunkRemeshSettings {
+    /// How many dirty chunks are remeshed per frame, the rest wait for the next frames.
+    pub max_chunks_per_frame: usize,
+}
 
-#[derive(serde::Deserialize, bevy::reflect::TypeUuid, bevy::reflect::TypePath)]
-#[uuid = "0403839f-b7bf-4fa6-a94d-3d91ffe26cec"]
-struct Size {
-    w: f32,
-    h: f32,
+impl Default for ChunkRemeshSettings {
+    fn default() -> Self {
+        Self { max_chunks_per_frame: 4 }
+    }
 }
 
+/// Level of detail of a chunk mesh: 0 is full detail, level n merges 2^n voxels along each axis.
+/// Only full detail chunks have colliders.
+#[derive(Component, Default, Clone, Copy, Eq, PartialEq)]
+struct ChunkLod(u32);
 
-#[derive(serde::Deserialize, bevy::reflect::TypeUuid, bevy::reflect::TypePath)]
-#[uuid = "535ceaaa-8372-4992-b857-272c19c60964"]
-struct ImageDescription {
-    frame: Frame,
+#[derive(Resource)]
+pub struct ChunkLodSettings {
+    /// Distances in chunks from the player where 2x, 4x and 8x downsampled meshes start.
+    pub distances: [f32; 3],
 }
 
-#[derive(serde::Deserialize, bevy::reflect::TypeUuid, bevy::reflect::TypePath)]
-#[uuid = "3bfc7636-53f0-4590-96fa-363602bff14f"]
-struct Frame {
-    x: f32,
-    y: f32,
-    w: f32,
-    h: f32,
+impl Default for ChunkLodSettings {
+    fn default() -> Self {
+        Self { distances: [2.0, 3.0, 4.0] }
+    }
 }
 
-#[derive(Resource, Clone, bevy::reflect::TypePath)]
-struct TextureAtlasHandle(Handle<TextureAtlas>);
+/// One material per mesh layer, see [`MeshLayer`].
+#[derive(Resource)]
+struct ChunkMaterials {
+    opaque: Handle<StandardMaterial>,
+    cutout: Handle<StandardMaterial>,
+    translucent: Handle<StandardMaterial>,
+    emissive: Handle<StandardMaterial>,
+    water: Handle<WaterMaterial>,
+}
 
-#[derive(Component)]
-struct Cube;
+impl ChunkMaterials {
+    /// Adds the material of a layer to the entity of a chunk sub-mesh.
+    fn insert(&self, layer: MeshLayer, entity: &mut EntityCommands) {
+        match layer {
+            MeshLayer::Opaque => entity.insert(self.opaque.clone()),
+            MeshLayer::Cutout => entity.insert(self.cutout.clone()),
+            MeshLayer::Translucent => entity.insert(self.translucent.clone()),
+            MeshLayer::Emissive => entity.insert(self.emissive.clone()),
+            MeshLayer::Water => entity.insert(self.water.clone()),
+        };
+    }
+}
 
 #[derive(Resource)]
 struct AtlasLoading {
     loaded: bool,
-    handle: TextureAtlasHandle,
-    atlas: Option<TextureAtlas>,
+    textures: BlockTextureLoading,
+    blocks: Handle<BlockList>,
+    /// Grass and foliage colormaps.
+    colormaps: [Handle<Image>; 2],
+    atlas: Option<BlockAtlas>,
 }
 
 fn load_atlas(asset_server: Res<AssetServer>, mut commands: Commands) {
-    let handle = TextureAtlasHandle(asset_server.load("textures/spritesheet.json"));
-
-    commands.insert_resource(AtlasLoading { handle, loaded: false, atlas: None })
+    let textures = BlockTextureLoading::start(&asset_server);
+    let blocks = asset_server.load("default.blocks.json");
+    let colormaps = [
+        asset_server.load("textures/colormap/grass.png"),
+        asset_server.load("textures/colormap/foliage.png"),
+    ];
+
+    commands.insert_resource(AtlasLoading { textures, blocks, colormaps, loaded: false, atlas: None })
 }
 
 
+#[allow(clippy::too_many_arguments)]
 fn asset_loaded(
-    commands: Commands,
+    mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut images: ResMut<Assets<Image>>,
+    animations: Res<Assets<TextureAnimation>>,
+    mut block_lists: ResMut<Assets<BlockList>>,
     mut atlas_loading: ResMut<AtlasLoading>,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<StandardMaterial>>,
+    mut registry: ResMut<BlockRegistry>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    water_materials: ResMut<Assets<WaterMaterial>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    chunk_map: ResMut<ChunkMap>,
 ) {
     if !atlas_loading.loaded
-        && asset_server.get_load_state(atlas_loading.handle.0.clone_weak()) == LoadState::Loaded
+        && atlas_loading.textures.is_loaded(&asset_server)
+        && asset_server.get_load_state(atlas_loading.blocks.clone_weak()) == LoadState::Loaded
+        && asset_server.get_group_load_state(atlas_loading.colormaps.iter().map(|handle| handle.id())) == LoadState::Loaded
     {
-        if let Some(atlas) = atlases.remove(atlas_loading.handle.0.id()) {
-            generate_world(commands, asset_server, meshes, materials, &atlas);
+        if let Some(block_list) = block_lists.remove(atlas_loading.blocks.id()) {
+            let [grass, foliage] = atlas_loading.colormaps.each_ref().map(|handle| images.get(handle).unwrap());
+            let tint = BiomeTint::new(grass, foliage).expect("Colormaps must be RGBA images");
+            let atlas = atlas_loading.textures.build(&asset_server, &mut images, &animations);
+            *registry = BlockRegistry::new(block_list);
+            atlas.report_missing_textures(&registry);
+            commands.insert_resource(BlockIcons(texture_atlases.add(atlas.icons(&registry))));
+            commands.insert_resource(item_meshes(&registry, &atlas, &tint, &mut meshes, &mut materials));
+            generate_world(commands, meshes, materials, water_materials, chunk_map, &registry, &atlas, tint);
 
             atlas_loading.atlas = Some(atlas);
             atlas_loading.loaded = true;
@@ -131,20 +204,107 @@ fn asset_loaded(
 }
 
 
+/// Meshes of every block for dropped items, with the tint of the world origin.
+fn item_meshes(
+    registry: &BlockRegistry,
+    atlas: &BlockAtlas,
+    tint: &BiomeTint,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) -> ItemMeshes {
+    let block_meshes = registry.iter()
+        .map(|block| {
+            if !block.is_visible() {
+                return None;
+            }
+            let mut builder = MeshBuilder::default();
+            for quad in block.model.quads() {
+                let color = block.tint(quad.normal).map_or([1.0; 4], |colormap| tint.color(colormap, 0.0, 0.0));
+                builder.push_quad(
+                    quad.positions.map(|position| position.to_array()),
+                    QUAD_INDICES,
+                    quad.normal,
+                    atlas.sub_uv(block.texture(quad.normal), quad.tex_coords),
+                    [color; 4],
+                );
+            }
+            builder.build(Vec3::splat(0.5)).map(|mesh| meshes.add(mesh))
+        })
+        .collect();
+
+    ItemMeshes {
+        meshes: block_meshes,
+        material: materials.add(StandardMaterial {
+            base_color_texture: Some(atlas.image.clone()),
+            alpha_mode: AlphaMode::Mask(0.5),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+        emissive_material: materials.add(StandardMaterial {
+            base_color_texture: Some(atlas.image.clone()),
+            emissive: Color::rgb_linear(2.0, 2.0, 2.0),
+            emissive_texture: Some(atlas.image.clone()),
+            alpha_mode: AlphaMode::Mask(0.5),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+    }
+}
+
+fn animate_block_textures(
+    time: Res<Time>,
+    mut atlas_loading: ResMut<AtlasLoading>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if let Some(atlas) = &mut atlas_loading.atlas {
+        atlas.animate(time.elapsed_seconds(), &mut images);
+    }
+}
+
+
+#[allow(clippy::too_many_arguments)]
 fn generate_world(mut commands: Commands,
-                  asset_server: Res<AssetServer>,
                   mut meshes: ResMut<Assets<Mesh>>,
                   mut materials: ResMut<Assets<StandardMaterial>>,
-                  atlas: &TextureAtlas) {
-    let texture_handle = asset_server.load("textures/spritesheet.png");
-
-    let material_handle = materials.add(StandardMaterial {
-        base_color_texture: Some(texture_handle),
-        // MSAA causes graphical artifacts with alpha_mode
-        alpha_mode: AlphaMode::Mask(0.5),
-        perceptual_roughness: 1.0,
-        ..default()
-    });
+                  mut water_materials: ResMut<Assets<WaterMaterial>>,
+                  mut chunk_map: ResMut<ChunkMap>,
+                  registry: &BlockRegistry,
+                  atlas: &BlockAtlas,
+                  tint: BiomeTint) {
+    let texture_handle = atlas.image.clone();
+
+    let chunk_materials = ChunkMaterials {
+        opaque: materials.add(StandardMaterial {
+            base_color_texture: Some(texture_handle.clone()),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+        cutout: materials.add(StandardMaterial {
+            base_color_texture: Some(texture_handle.clone()),
+            // MSAA causes graphical artifacts with alpha_mode
+            alpha_mode: AlphaMode::Mask(0.5),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+        // above 1 so emissive blocks bloom through the HDR camera
+        emissive: materials.add(StandardMaterial {
+            base_color_texture: Some(texture_handle.clone()),
+            emissive: Color::rgb_linear(2.0, 2.0, 2.0),
+            emissive_texture: Some(texture_handle.clone()),
+            alpha_mode: AlphaMode::Mask(0.5),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+        // Blended surfaces are not written to the depth and motion vector prepasses,
+        // so TAA reprojects whatever is behind them instead of smearing the surface itself.
+        translucent: materials.add(StandardMaterial {
+            base_color_texture: Some(texture_handle),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 0.3,
+            ..default()
+        }),
+        water: water_materials.add(WaterMaterial::default()),
+    };
 
     let now = SystemTime::now();
     let fbm = Fbm::<OpenSimplex>::default();
@@ -161,6 +321,13 @@ fn generate_world(mut commands: Commands,
 
     let mut chunks: HashMap<[i32; 3], Vec<MaterialVoxel>> = HashMap::new();
 
+    let grass = registry.id("grass");
+    let dirt = registry.id("dirt");
+    let stone = registry.id("stone");
+    let sand = registry.id("sand");
+    let water = registry.id("water");
+    let short_grass = registry.id("short_grass");
+
     println!("Noise gen time: {}ms", now.elapsed().unwrap().as_millis());
     let now = SystemTime::now();
 
@@ -181,28 +348,37 @@ fn generate_world(mut commands: Commands,
                     let under_surface = builder_world_y < height.round() as i32;
                     let surface = builder_world_y == height.round() as i32;
 
-                    let voxel_type = if surface {
-                        if biome_value < -0.2 {
-                            VoxelType::Dirt
+                    let block = if surface {
+                        if builder_world_y <= SEA_LEVEL {
+                            sand
+                        } else if biome_value < -0.2 {
+                            dirt
                         } else if biome_value > 0.2 {
-                            VoxelType::Stone
+                            stone
                         } else {
-                            VoxelType::Grass
+                            grass
                         }
                     } else if under_surface {
                         // let val = fbm2.get([x as f64, y as f64, z as f64]);
                         // if val > 0.8 {
-                            VoxelType::Sand
+                            sand
                         // } else if val > 0.6 {
-                        //     VoxelType::Stone
+                        //     stone
                         // } else {
-                        //     VoxelType::Empty
+                        //     BlockId::AIR
                         // }
+                    } else if builder_world_y == height.round() as i32 + 1
+                        && builder_world_y > SEA_LEVEL + 1
+                        && (-0.2..=0.2).contains(&biome_value)
+                        && column_hash(builder_world_x, builder_world_z) % 8 == 0 {
+                        short_grass
+                    } else if builder_world_y <= SEA_LEVEL {
+                        water
                     } else {
-                        VoxelType::Empty
+                        BlockId::AIR
                     };
 
-                    samples.push(MaterialVoxel(voxel_type));
+                    samples.push(MaterialVoxel(block));
                 }
 
                 chunks.insert([current_chunk_x, current_chunk_y, current_chunk_z], samples);
@@ -220,61 +396,112 @@ fn generate_world(mut commands: Commands,
         let world_z = point[1].floor() - (VISIBLE_CHUNK_DISTANCE * CHUNK_SIZE) as f32;
         let world_y = builder.get_value((world_x as i32 + CHUNKS_COUNT_X / 2 * CHUNK_SIZE) as usize, (world_z as i32 + CHUNKS_COUNT_Z / 2 * CHUNK_SIZE) as usize) as f32 * BUILDER_HEIGHT_SCALE;
 
-        generate_tree(Vec3::new(world_x, world_y.round(), world_z), &mut chunks);
+        generate_tree(Vec3::new(world_x, world_y.round(), world_z), registry, &mut chunks);
     }
 
     println!("Fill time: {}ms", now.elapsed().unwrap().as_millis());
     let now = SystemTime::now();
 
+    let mut emitters = Vec::new();
+    for (pos, samples) in &chunks {
+        for (index, voxel) in samples.iter().enumerate() {
+            let local = SampleShape::delinearize(index as u32);
+            let interior = local.iter().all(|coordinate| (1..=CHUNK_SIZE as u32).contains(coordinate));
+            if interior && registry.get(voxel.0).light_emission > 0 {
+                emitters.push(IVec3::from(*pos) * CHUNK_SIZE + UVec3::from(local).as_ivec3());
+            }
+        }
+    }
+    let light = LightMap::generate(
+        chunks.keys().copied(),
+        emitters.into_iter(),
+        |voxel| {
+            let (chunk, local) = interior_chunk(voxel);
+            chunks.get(&chunk).map(|samples| samples[SampleShape::linearize(local) as usize].0)
+        },
+        registry,
+    );
+
+    println!("Light gen time: {}ms", now.elapsed().unwrap().as_millis());
+    let now = SystemTime::now();
+
+    let context = MeshContext { registry, atlas, tint: &tint, light: &light };
+    let mut collider_time = 0;
     for (pos, samples) in chunks {
-        let (simple_mesh, generated) = generate_simple_mesh(&samples, atlas);
-
-        if generated > 0 {
-            spawn_pbr(
-                &mut commands,
-                &mut meshes,
-                simple_mesh,
-                material_handle.clone(),
-                Transform::from_translation(Vec3::new(
-                    (pos[0] * CHUNK_SIZE) as f32,
-                    (pos[1] * CHUNK_SIZE) as f32,
-                    (pos[2] * CHUNK_SIZE) as f32)),
-                samples,
-            );
+        let origin = Vec3::new(
+            (pos[0] * CHUNK_SIZE) as f32,
+            (pos[1] * CHUNK_SIZE) as f32,
+            (pos[2] * CHUNK_SIZE) as f32);
+        let near = pos.iter().all(|coordinate| coordinate.abs() <= MESHED_CHUNK_DISTANCE);
+        let (chunk_meshes, collider) = if near {
+            let collider_start = SystemTime::now();
+            let collider = generate_collider(&samples, registry);
+            collider_time += collider_start.elapsed().unwrap().as_micros();
+            (generate_simple_mesh(&samples, origin, &context), collider)
+        } else {
+            (ChunkMeshes { layers: Default::default() }, None)
+        };
+
+        // Empty chunks are spawned too: building into them must find their samples.
+        let entity = spawn_pbr(
+            &mut commands,
+            &mut meshes,
+            chunk_meshes,
+            &chunk_materials,
+            Transform::from_translation(origin),
+            samples,
+            collider,
+        );
+        if !near {
+            commands.entity(entity).insert(DirtyChunk);
         }
+        chunk_map.0.insert(pos, entity);
     }
 
+    commands.insert_resource(chunk_materials);
+    commands.insert_resource(tint);
+    commands.insert_resource(light);
+
     println!("Mesh gen time: {}ms", now.elapsed().unwrap().as_millis());
+    println!("Collider gen time: {}ms", collider_time / 1000);
+}
+
+/// Cheap deterministic noise per column, padding samples of neighbouring chunks must agree on it.
+fn column_hash(x: i32, z: i32) -> u32 {
+    let hash = (x as u32).wrapping_mul(0x9E37_79B1) ^ (z as u32).wrapping_mul(0x85EB_CA77);
+    (hash ^ (hash >> 15)).wrapping_mul(0xC2B2_AE3D) >> 16
 }
 
-fn generate_tree(origin: Vec3, chunks: &mut HashMap<[i32; 3], Vec<MaterialVoxel>>) {
+fn generate_tree(origin: Vec3, registry: &BlockRegistry, chunks: &mut HashMap<[i32; 3], Vec<MaterialVoxel>>) {
+    let leaves = registry.id("oak_leaves");
+    let log = registry.id("oak_log");
     match rand::thread_rng().gen_range(0..=1) {
         0 => {
-            change_voxel(origin + Vec3::new(0.0, 4.0, 0.0), VoxelType::OakLeaves, chunks);
+            change_voxel(origin + Vec3::new(0.0, 4.0, 0.0), leaves, chunks);
             for x in -1..=1 {
                 for z in -1..=1 {
-                    change_voxel(origin + Vec3::new(x as f32, 3.0, z as f32), VoxelType::OakLeaves, chunks);
+                    change_voxel(origin + Vec3::new(x as f32, 3.0, z as f32), leaves, chunks);
                 }
             }
             for z in 0..=2 {
-                change_voxel(origin + Vec3::new(0.0, z as f32, 0.0), VoxelType::OakLog, chunks);
+                change_voxel(origin + Vec3::new(0.0, z as f32, 0.0), log, chunks);
             }
         }
         1 => {
-            change_voxel(origin + Vec3::new(0.0, 6.0, 0.0), VoxelType::OakLeaves, chunks);
+            change_voxel(origin + Vec3::new(0.0, 6.0, 0.0), leaves, chunks);
             for x in -1..=1 {
                 for z in -1..=1 {
-                    change_voxel(origin + Vec3::new(x as f32, 3.0, z as f32), VoxelType::OakLeaves, chunks);
-                    change_voxel(origin + Vec3::new(x as f32, 5.0, z as f32), VoxelType::OakLeaves, chunks);
+                    change_voxel(origin + Vec3::new(x as f32, 3.0, z as f32), leaves, chunks);
+                    change_voxel(origin + Vec3::new(x as f32, 5.0, z as f32), leaves, chunks);
                 }
             }
             for x in -2..=2 {
                 for z in -2..=2 {
-                    change_voxel(origin + Vec3::new(x as f32, 4.0, z as f32), VoxelType::OakLeaves, chunks);
+                    change_voxel(origin + Vec3::new(x as f32, 4.0, z as f32), leaves, chunks);
                 }
             }
             for z in 0..=4 {
-                change_voxel(origin + Vec3::new(0.0, z as f32, 0.0), VoxelType::OakLog, chunks);
+                change_voxel(origin + Vec3::new(0.0, z as f32, 0.0), log, chunks);
             }
         }
         _ => {
@@ -283,249 +510,756 @@ fn generate_tree(origin: Vec3, chunks: &mut HashMap<[i32; 3], Vec<MaterialVoxel>
     }
 }
 
-fn change_voxel(world_point: Vec3, voxel_type: VoxelType, chunks: &mut HashMap<[i32; 3], Vec<MaterialVoxel>>) {
-    let chunk_position = [
-        (world_point.x / CHUNK_SIZE as f32).floor() as i32,
-        (world_point.y / CHUNK_SIZE as f32).floor() as i32,
-        (world_point.z / CHUNK_SIZE as f32).floor() as i32];
-    let samples = chunks.get_mut(&chunk_position);
-    if let Some(samples) = samples {
+fn change_voxel(world_point: Vec3, block: BlockId, chunks: &mut HashMap<[i32; 3], Vec<MaterialVoxel>>) {
+    // sample index 0 is the padding voxel, so generation coordinates are shifted by one
+    let voxel = world_point.floor().as_ivec3() + IVec3::ONE;
+    let mut found = false;
+    for (chunk_position, local) in chunks_containing_voxel(voxel) {
+        if let Some(samples) = chunks.get_mut(&chunk_position) {
+            samples[SampleShape::linearize(local) as usize] = MaterialVoxel(block);
+            found = true;
+        }
+    }
+    if !found {
+        println!("Chunk not found for voxel {voxel}");
+    }
+}
 
-        let voxel_x = (world_point.x - (chunk_position[0] * CHUNK_SIZE) as f32).floor() as u32;
-        let voxel_y = (world_point.y - (chunk_position[1] * CHUNK_SIZE) as f32).floor() as u32;
-        let voxel_z = (world_point.z - (chunk_position[2] * CHUNK_SIZE) as f32).floor() as u32;
-        let index = SampleShape::linearize([voxel_x + 1, voxel_y + 1, voxel_z + 1]);
-        samples[index as usize] = MaterialVoxel(voxel_type);
-    } else {
-        println!("Chunk not found {chunk_position:?}");
+/// Chunk storing a world voxel in its interior and the voxel's sample position in it.
+fn interior_chunk(voxel: IVec3) -> ([i32; 3], [u32; 3]) {
+    let chunk = (voxel - 1).div_euclid(IVec3::splat(CHUNK_SIZE));
+    (chunk.to_array(), (voxel - chunk * CHUNK_SIZE).as_uvec3().to_array())
+}
+
+/// Every chunk keeps a 1-voxel padding copy of its neighbours, so a voxel on a chunk border
+/// is stored in up to 8 chunks. Returns each of those chunks with the voxel's sample coordinates.
+fn chunks_containing_voxel(voxel: IVec3) -> Vec<([i32; 3], [u32; 3])> {
+    let axis = |v: i32| {
+        // interior samples are 1..=CHUNK_SIZE, 0 and CHUNK_SIZE + 1 are padding
+        let chunk = (v - 1).div_euclid(CHUNK_SIZE);
+        let local = v - chunk * CHUNK_SIZE;
+        let mut result = vec![(chunk, local as u32)];
+        if local == 1 {
+            result.push((chunk - 1, (local + CHUNK_SIZE) as u32));
+        }
+        if local == CHUNK_SIZE {
+            result.push((chunk + 1, 0));
+        }
+        result
+    };
+
+    let mut result = Vec::new();
+    for (chunk_x, local_x) in axis(voxel.x) {
+        for (chunk_y, local_y) in axis(voxel.y) {
+            for (chunk_z, local_z) in axis(voxel.z) {
+                result.push(([chunk_x, chunk_y, chunk_z], [local_x, local_y, local_z]));
+            }
+        }
     }
+    result
 }
 
 #[derive(Component)]
-struct ChunkInfo {
+pub struct ChunkInfo {
     samples: Vec<MaterialVoxel>,
 }
 
 fn dig_event_handler(
-    mut query: Query<(Entity, &mut ChunkInfo, &Transform)>,
+    mut query: Query<&mut ChunkInfo>,
+    chunk_map: Res<ChunkMap>,
+    registry: Res<BlockRegistry>,
+    mut light: ResMut<LightMap>,
     mut commands: Commands,
-    atlas_loading: Res<AtlasLoading>,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut ev: EventReader<DigEvent>,
 ) {
-    if let Some(atlas) = &atlas_loading.atlas {
-        for ev in ev.iter() {
-            println!("Handle event {}", ev.world_position);
-            for (entity, mut chunk, transform) in query.iter_mut() {
-                let position = voxel_position_from_world(transform.translation, ev.world_position);
+    for ev in ev.iter() {
+        let voxel = ev.world_position.floor().as_ivec3();
+        let new_voxel = match ev.event_type {
+            DigEventType::Dig => EMPTY,
+            DigEventType::Build(block) => MaterialVoxel(block)
+        };
+
+        // update the voxel and the padding copies of it, remeshing happens in remesh_dirty_chunks
+        for (chunk_position, local) in chunks_containing_voxel(voxel) {
+            let Some(&entity) = chunk_map.0.get(&chunk_position) else {
+                continue;
+            };
+            let Ok(mut chunk) = query.get_mut(entity) else {
+                continue;
+            };
+
+            chunk.samples[SampleShape::linearize(local) as usize] = new_voxel;
+            commands.entity(entity).insert(DirtyChunk);
+        }
 
-                println!("Check pos! {position}");
+        let relit = light.update(
+            voxel,
+            |voxel| {
+                let (chunk_position, local) = interior_chunk(voxel);
+                let chunk = query.get(*chunk_map.0.get(&chunk_position)?).ok()?;
+                Some(chunk.samples[SampleShape::linearize(local) as usize].0)
+            },
+            &registry,
+        );
+        // faces next to a relit voxel can be in the chunks holding it as padding too
+        let relit_chunks: HashSet<[i32; 3]> = relit.into_iter()
+            .flat_map(chunks_containing_voxel)
+            .map(|(chunk_position, _)| chunk_position)
+            .collect();
+        for chunk_position in relit_chunks {
+            if let Some(&entity) = chunk_map.0.get(&chunk_position) {
+                commands.entity(entity).insert(DirtyChunk);
+            }
+        }
+    }
+}
 
-                if !(position.x >= 0.0 && position.x <= 33.0 &&
-                    position.y >= 0.0 && position.y <= 33.0 &&
-                    position.z >= 0.0 && position.z <= 33.0) {
-                    continue;
+/// Read access to the voxels of loaded chunks by world voxel position.
+#[derive(SystemParam)]
+pub struct VoxelWorld<'w, 's> {
+    chunk_map: Res<'w, ChunkMap>,
+    registry: Res<'w, BlockRegistry>,
+    chunks: Query<'w, 's, &'static ChunkInfo>,
+}
+
+impl VoxelWorld<'_, '_> {
+    fn voxel(&self, voxel: IVec3) -> Option<BlockId> {
+        let (chunk_position, local) = interior_chunk(voxel);
+        let entity = self.chunk_map.0.get(&chunk_position)?;
+        let chunk = self.chunks.get(*entity).ok()?;
+        Some(chunk.samples[SampleShape::linearize(local) as usize].0)
+    }
+
+    /// Definition of the block at a world position, `None` outside of the loaded chunks.
+    pub fn block_at(&self, position: Vec3) -> Option<&BlockDefinition> {
+        self.voxel(position.floor().as_ivec3()).map(|block| self.registry.get(block))
+    }
+
+    /// Block at a world position, `None` outside of the loaded chunks.
+    pub fn block_id_at(&self, position: Vec3) -> Option<BlockId> {
+        self.voxel(position.floor().as_ivec3())
+    }
+
+    /// Top of the highest visible block in a column, where rain and snow stop falling, or the
+    /// bottom of the world for empty columns. `None` while none of the column is loaded.
+    pub fn precipitation_height(&self, x: i32, z: i32) -> Option<i32> {
+        let top = (VISIBLE_CHUNK_DISTANCE + 1) * CHUNK_SIZE;
+        let bottom = -VISIBLE_CHUNK_DISTANCE * CHUNK_SIZE + 1;
+        let mut loaded = false;
+        for y in (bottom..=top).rev() {
+            if let Some(block) = self.voxel(IVec3::new(x, y, z)) {
+                if self.registry.get(block).is_visible() {
+                    return Some(y + 1);
                 }
+                loaded = true;
+            }
+        }
+        loaded.then_some(bottom)
+    }
 
-                println!("Must update chunk! {} trans={}", position, transform.translation);
-                let voxel_index = SampleShape::linearize(position.as_uvec3().to_array());
+    /// Steps through the voxels along a ray and returns the first selectable one,
+    /// with the normal of the face the ray entered it through.
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<(IVec3, IVec3)> {
+        let mut voxel = origin.floor().as_ivec3();
+        let step = direction.signum().as_ivec3();
+        let delta = direction.recip().abs();
+        let mut next = Vec3::select(
+            direction.cmpgt(Vec3::ZERO),
+            (voxel.as_vec3() + Vec3::ONE - origin) * delta,
+            (origin - voxel.as_vec3()) * delta,
+        );
+        let mut normal = IVec3::ZERO;
+        let mut distance = 0.0;
+
+        while distance <= max_distance {
+            if self.voxel(voxel).is_some_and(|block| self.registry.get(block).selectable) {
+                return Some((voxel, normal));
+            }
+
+            let axis = if next.x < next.y && next.x < next.z {
+                0
+            } else if next.y < next.z {
+                1
+            } else {
+                2
+            };
+            voxel[axis] += step[axis];
+            distance = next[axis];
+            next[axis] += delta[axis];
+            normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+        }
+        None
+    }
+}
+
+/// Directions of the six chunks sharing a face with a chunk.
+const NEIGHBOUR_DIRECTIONS: [IVec3; 6] = [IVec3::NEG_X, IVec3::X, IVec3::NEG_Y, IVec3::Y, IVec3::NEG_Z, IVec3::Z];
+
+fn update_chunk_lod(
+    players: Query<&Transform, With<LogicalPlayer>>,
+    chunks: Query<(Entity, &Transform, &ChunkLod), With<ChunkInfo>>,
+    chunk_map: Res<ChunkMap>,
+    settings: Res<ChunkLodSettings>,
+    mut commands: Commands,
+) {
+    if let Ok(player) = players.get_single() {
+        for (entity, transform, lod) in chunks.iter() {
+            let chunk_center = transform.translation + Vec3::splat(CHUNK_SIZE as f32 / 2.0 + 1.0);
+            let distance = chunk_center.distance(player.translation) / CHUNK_SIZE as f32;
+            let level = settings.distances.iter().filter(|start| distance >= **start).count() as u32;
+
+            if level != lod.0 {
+                commands.entity(entity).insert((ChunkLod(level), DirtyChunk));
+
+                // distant neighbours pad their borders with this chunk only while the levels match
+                let position = (transform.translation / CHUNK_SIZE as f32).round().as_ivec3();
+                for direction in NEIGHBOUR_DIRECTIONS {
+                    let Some(&neighbour) = chunk_map.0.get(&(position + direction).to_array()) else {
+                        continue;
+                    };
+                    if chunks.get(neighbour).is_ok_and(|(_, _, lod)| lod.0 > 0) {
+                        commands.entity(neighbour).insert(DirtyChunk);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn remesh_dirty_chunks(
+    query: Query<(Entity, &Transform, &ChunkInfo, &ChunkLod, &ChunkMeshEntities), With<DirtyChunk>>,
+    chunks: Query<(&ChunkInfo, &ChunkLod)>,
+    chunk_map: Res<ChunkMap>,
+    settings: Res<ChunkRemeshSettings>,
+    mut commands: Commands,
+    registry: Res<BlockRegistry>,
+    atlas_loading: Res<AtlasLoading>,
+    tint: Option<Res<BiomeTint>>,
+    light: Res<LightMap>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if let (Some(atlas), Some(tint)) = (&atlas_loading.atlas, tint) {
+        let context = MeshContext { registry: &registry, atlas, tint: &tint, light: &light };
+        let mut dirty: Vec<_> = query.iter().collect();
+        // full detail chunks first, the player stands on their colliders
+        dirty.sort_by_key(|(_, _, _, lod, _)| lod.0);
+
+        for (entity, transform, chunk, lod, mesh_entities) in dirty.into_iter().take(settings.max_chunks_per_frame) {
+            let origin = transform.translation;
+            let (chunk_meshes, collider) = if lod.0 == 0 {
+                let collider = generate_collider(&chunk.samples, &registry);
+                (generate_simple_mesh(&chunk.samples, origin, &context), collider)
+            } else {
+                let scale = 1 << lod.0;
+                let position = (origin / CHUNK_SIZE as f32).round().as_ivec3();
+                let neighbours = NEIGHBOUR_DIRECTIONS.map(|direction| {
+                    let entity = chunk_map.0.get(&(position + direction).to_array())?;
+                    let (neighbour, neighbour_lod) = chunks.get(*entity).ok()?;
+                    (neighbour_lod == lod).then_some(neighbour.samples.as_slice())
+                });
+                let (samples, shape) = downsample(&chunk.samples, neighbours, scale, &registry);
+                (generate_mesh(&samples, &shape, scale, origin, &context), None)
+            };
+
+            for (mesh_entity, mesh) in mesh_entities.0.iter().zip(chunk_meshes.layers) {
+                set_chunk_mesh(&mut commands.entity(*mesh_entity), &mut meshes, mesh);
+            }
+
+            let mut entity = commands.entity(entity);
+            entity.remove::<DirtyChunk>();
+            match collider {
+                Some(collider) => entity.insert(collider),
+                None => entity.remove::<Collider>(),
+            };
+        }
+    }
+}
 
-                chunk.samples[voxel_index as usize] = match ev.event_type {
-                    DigEventType::Dig => EMPTY,
-                    DigEventType::Build => MaterialVoxel(VoxelType::Cobblestone)
-                };
+/// Downsamples chunk samples for a distant level of detail. Every cell takes the topmost full cube
+/// of its block so surfaces keep their top textures, other models are too small to matter.
+/// `neighbours` are the samples of the chunks in [`NEIGHBOUR_DIRECTIONS`] that have the same level
+/// of detail: the padding facing them is downsampled from their cells, so no faces are emitted
+/// between the two chunks. The padding facing other levels is left empty, the border faces then
+/// close the chunk and cover the cracks where its cells don't line up with the neighbour's.
+fn downsample(
+    samples: &[MaterialVoxel],
+    neighbours: [Option<&[MaterialVoxel]>; 6],
+    factor: u32,
+    registry: &BlockRegistry,
+) -> (Vec<MaterialVoxel>, RuntimeShape<u32, 3>) {
+    let cells = CHUNK_SIZE as u32 / factor;
+    let shape = RuntimeShape::<u32, 3>::new([cells + 2; 3]);
+    let mut result = vec![EMPTY; shape.usize()];
+
+    for z in 0..cells {
+        for y in 0..cells {
+            for x in 0..cells {
+                let base = [1 + x * factor, 1 + y * factor, 1 + z * factor];
+                result[shape.linearize([x + 1, y + 1, z + 1]) as usize] = downsample_cell(samples, base, factor, registry);
+            }
+        }
+    }
 
-                let (simple_mesh, generated) = generate_simple_mesh(&chunk.samples, atlas);
-                let mesh_handle = meshes.add(simple_mesh.clone());
+    for (direction, neighbour) in NEIGHBOUR_DIRECTIONS.into_iter().zip(neighbours) {
+        let Some(neighbour) = neighbour else {
+            continue;
+        };
+        // coordinates with `along` on the axis of the direction and `u`, `v` on the other two
+        let at = |along: u32, u: u32, v: u32| match direction {
+            IVec3 { x: 0, y: 0, .. } => [u, v, along],
+            IVec3 { x: 0, .. } => [u, along, v],
+            _ => [along, u, v],
+        };
+        let (padding, neighbour_cell) = if direction.min_element() < 0 { (0, cells - 1) } else { (cells + 1, 0) };
+        for v in 0..cells {
+            for u in 0..cells {
+                let base = at(1 + neighbour_cell * factor, 1 + u * factor, 1 + v * factor);
+                result[shape.linearize(at(padding, u + 1, v + 1)) as usize] = downsample_cell(neighbour, base, factor, registry);
+            }
+        }
+    }
+    (result, shape)
+}
 
-                if generated > 0 {
-                    commands.entity(entity)
-                        .insert(mesh_handle)
-                        .insert(Collider::from_bevy_mesh(&simple_mesh, &ComputedColliderShape::TriMesh).unwrap());
+/// Topmost full cube among the `factor`³ samples starting at `base`.
+fn downsample_cell(samples: &[MaterialVoxel], base: [u32; 3], factor: u32, registry: &BlockRegistry) -> MaterialVoxel {
+    for dy in (0..factor).rev() {
+        for dz in 0..factor {
+            for dx in 0..factor {
+                let voxel = samples[SampleShape::linearize([base[0] + dx, base[1] + dy, base[2] + dz]) as usize];
+                if voxel != EMPTY && registry.get(voxel.0).model == BlockModel::Cube {
+                    return voxel;
                 }
             }
         }
     }
+    EMPTY
 }
 
-fn voxel_position_from_world(chunk_translation: Vec3, world_position: Vec3) -> Vec3 {
-    (world_position - chunk_translation).floor()
+/// Chunk sub-mesh a block is rendered in, every layer has its own material.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum MeshLayer {
+    Opaque,
+    /// Alpha tested blocks such as leaves.
+    Cutout,
+    /// Alpha blended blocks such as glass and ice.
+    Translucent,
+    /// Light sources such as glowstone and torches, alpha tested.
+    Emissive,
+    /// Water surfaces with their own shader, alpha blended.
+    Water,
 }
 
-fn generate_simple_mesh(
+const MESH_LAYERS: [MeshLayer; 5] = [MeshLayer::Opaque, MeshLayer::Cutout, MeshLayer::Translucent, MeshLayer::Emissive, MeshLayer::Water];
+
+impl MeshLayer {
+    fn of(block: &BlockDefinition) -> Self {
+        if block.emissive {
+            return MeshLayer::Emissive;
+        }
+        if block.water {
+            return MeshLayer::Water;
+        }
+        match block.visibility {
+            BlockVisibility::Cutout => MeshLayer::Cutout,
+            BlockVisibility::Translucent => MeshLayer::Translucent,
+            _ => MeshLayer::Opaque,
+        }
+    }
+
+    fn is_blended(self) -> bool {
+        matches!(self, MeshLayer::Translucent | MeshLayer::Water)
+    }
+
+    /// Transparent meshes are sorted by their origin, so blended sub-meshes are placed
+    /// at the chunk center instead of its corner.
+    fn origin(self) -> Vec3 {
+        if self.is_blended() {
+            Vec3::splat(CHUNK_SIZE as f32 / 2.0 + 1.0)
+        } else {
+            Vec3::ZERO
+        }
+    }
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    indices: Vec<u32>,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+}
+
+impl MeshBuilder {
+    fn push_quad(&mut self, positions: [[f32; 3]; 4], indices: [u32; 6], normal: Vec3, uvs: [[f32; 2]; 4], colors: [[f32; 4]; 4]) {
+        let start = self.positions.len() as u32;
+        self.indices.extend(indices.map(|index| start + index));
+        self.positions.extend_from_slice(&positions);
+        self.normals.extend_from_slice(&[normal.to_array(); 4]);
+        self.uvs.extend_from_slice(&uvs);
+        self.colors.extend_from_slice(&colors);
+    }
+
+    fn build(self, origin: Vec3) -> Option<Mesh> {
+        if self.positions.is_empty() {
+            return None;
+        }
+        let positions: Vec<[f32; 3]> = self.positions.into_iter()
+            .map(|position| (Vec3::from_array(position) - origin).to_array())
+            .collect();
+        let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        render_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        render_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        render_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        render_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        render_mesh.set_indices(Some(Indices::U32(self.indices)));
+        Some(render_mesh)
+    }
+}
+
+/// Render sub-meshes of a chunk indexed like [`MESH_LAYERS`].
+struct ChunkMeshes {
+    layers: [Option<Mesh>; MESH_LAYERS.len()],
+}
+
+/// Collider of a full detail chunk, built from its voxels rather than its meshes.
+fn generate_collider(samples: &[MaterialVoxel], registry: &BlockRegistry) -> Option<Collider> {
+    chunk_collider(|index| samples[index as usize].0, &SampleShape {}, registry)
+}
+
+/// Block data meshing looks up.
+struct MeshContext<'a> {
+    registry: &'a BlockRegistry,
+    atlas: &'a BlockAtlas,
+    tint: &'a BiomeTint,
+    light: &'a LightMap,
+}
+
+fn generate_simple_mesh(samples: &[MaterialVoxel], origin: Vec3, context: &MeshContext) -> ChunkMeshes {
+    generate_mesh(samples, &SampleShape {}, 1, origin, context)
+}
+
+/// Voxel as seen by block-mesh, resolved from the registry before meshing.
+#[derive(Clone, Copy)]
+struct MeshVoxel(VoxelVisibility);
+
+impl Voxel for MeshVoxel {
+    fn get_visibility(&self) -> VoxelVisibility {
+        self.0
+    }
+}
+
+/// Side overlays are pushed this far out of the face they cover.
+const OVERLAY_OFFSET: f32 = 0.002;
+
+/// Meshes a padded voxel grid whose cells are `scale` voxels wide, positions are in chunk samples.
+/// `origin` is the world position of the chunk, tints are sampled at world positions.
+/// Only full detail meshes are lit, distant ones are mostly open to the sky.
+fn generate_mesh<S: Shape<3, Coord = u32>>(
     samples: &[MaterialVoxel],
-    atlas: &TextureAtlas,
-) -> (Mesh, usize) {
+    shape: &S,
+    scale: u32,
+    origin: Vec3,
+    context: &MeshContext,
+) -> ChunkMeshes {
+    let MeshContext { registry, atlas, tint, light } = *context;
+    let brightness = |sample: IVec3| if scale == 1 {
+        light.brightness(origin.as_ivec3() + sample)
+    } else {
+        1.0
+    };
+    let vertex_colors = |colormap: Option<Colormap>, positions: [[f32; 3]; 4], brightness: f32| {
+        let colors = match colormap {
+            Some(colormap) => positions.map(|[x, _, z]| tint.color(colormap, origin.x + x, origin.z + z)),
+            None => [[1.0; 4]; 4],
+        };
+        colors.map(|[r, g, b, a]| [r * brightness, g * brightness, b * brightness, a])
+    };
+
     let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
+    let mesh_voxels: Vec<MeshVoxel> = samples.iter()
+        .map(|voxel| MeshVoxel(registry.get(voxel.0).voxel_visibility()))
+        .collect();
 
     let mut buffer = UnitQuadBuffer::new();
     visible_block_faces(
-        samples,
-        &SampleShape {},
+        &mesh_voxels,
+        shape,
         [0; 3],
-        [33; 3],
+        shape.as_array().map(|size| size - 1),
         &faces,
         &mut buffer,
     );
 
-    let num_indices = buffer.num_quads() * 6;
-    let num_vertices = buffer.num_quads() * 4;
-    let mut indices = Vec::with_capacity(num_indices);
-    let mut positions = Vec::with_capacity(num_vertices);
-    let mut normals = Vec::with_capacity(num_vertices);
-    let mut uvs = Vec::with_capacity(num_vertices);
-    let mut colors = Vec::with_capacity(num_vertices);
+    // the first cell starts at sample 1 whatever its size
+    let offset = 1.0 - scale as f32;
+
+    let mut layers: [MeshBuilder; MESH_LAYERS.len()] = Default::default();
     for (group, face) in buffer.groups.into_iter().zip(faces.into_iter()) {
         for quad in group.into_iter() {
-            let quad_positions = face.quad_mesh_positions(&quad.into(), 1.0);
-            indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
-            positions.extend_from_slice(&quad_positions);
-            normals.extend_from_slice(&face.quad_mesh_normals());
-
+            let block = registry.get(face_to_block(samples, shape, &quad));
             let normal = Vec3::from_array(face.quad_mesh_normals()[0]);
-            let voxel_type = face_to_voxel_type(samples, face, quad_positions);
+            // faces are lit by the voxel they look into
+            let face_brightness = brightness(UVec3::from(quad.minimum).as_ivec3() + normal.as_ivec3());
+            let quad_positions = face.quad_mesh_positions(&quad.into(), scale as f32)
+                .map(|position| position.map(|coordinate| coordinate + offset));
+            let quad_indices = face.quad_mesh_indices(0);
+
+            let frame_name = block.texture(normal);
+
+            layers[MeshLayer::of(block) as usize].push_quad(
+                quad_positions,
+                quad_indices,
+                normal,
+                atlas.uv(frame_name),
+                vertex_colors(block.tint(normal), quad_positions, face_brightness),
+            );
 
-            let default_color = [[1.0, 1.0, 1.0, 1.0]; 4];
-            let color = match voxel_type {
-                VoxelType::Grass => {
-                    if normal.y == 1.0 {
-                        [[0.1, 0.8, 0.1, 1.0]; 4]
-                    } else {
-                        default_color
-                    }
+            if normal.y == 0.0 {
+                if let (Some(overlay), Some(block_tint)) = (&block.textures.side_overlay, &block.tint) {
+                    let overlay_positions = quad_positions
+                        .map(|position| (Vec3::from_array(position) + normal * OVERLAY_OFFSET).to_array());
+                    layers[MeshLayer::Cutout as usize].push_quad(
+                        overlay_positions,
+                        quad_indices,
+                        normal,
+                        atlas.uv(overlay),
+                        vertex_colors(Some(block_tint.colormap), overlay_positions, face_brightness),
+                    );
                 }
-                VoxelType::OakLeaves => {
-                    [[0.1, 0.8, 0.1, 1.0]; 4]
-                },
-                _ => default_color
-            };
-            colors.extend_from_slice(&color);
-
-            let frame_name = voxel_texture_name(normal, voxel_type);
-
-            uvs.extend_from_slice(&atlas_uv(atlas, &atlas.frames.get(frame_name).unwrap().frame));
+            }
         }
     }
-    let generated = positions.len();
-    let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
-    render_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-    render_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-    render_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-    render_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
-    render_mesh.set_indices(Some(Indices::U32(indices.clone())));
-    (render_mesh, generated)
-}
-
-fn face_to_voxel_type(samples: &[MaterialVoxel], face: OrientedBlockFace, quad_positions: [[f32; 3]; 4]) -> VoxelType {
-    let normal = Vec3::from_array(face.quad_mesh_normals()[0]);
-    let quad_center = Vec3::new(
-        (quad_positions[0][0] + quad_positions[1][0] + quad_positions[2][0] + quad_positions[3][0]) / 4.0,
-        (quad_positions[0][1] + quad_positions[1][1] + quad_positions[2][1] + quad_positions[3][1]) / 4.0,
-        (quad_positions[0][2] + quad_positions[1][2] + quad_positions[2][2] + quad_positions[3][2]) / 4.0,
-    );
 
-    let block_center = quad_center - normal / 2.0;
+    // block-mesh sees models as empty voxels, so they are emitted here
+    let [size_x, size_y, size_z] = shape.as_array();
+    for z in 1..size_z - 1 {
+        for y in 1..size_y - 1 {
+            for x in 1..size_x - 1 {
+                let block = registry.get(samples[shape.linearize([x, y, z]) as usize].0);
+                let model = block.model;
+                if model == BlockModel::Cube {
+                    continue;
+                }
 
-    let voxel_index = SampleShape::linearize(block_center.floor().as_uvec3().to_array());
+                let block_origin = UVec3::new(x, y, z).as_vec3() * scale as f32 + Vec3::splat(offset);
+                let model_brightness = brightness(UVec3::new(x, y, z).as_ivec3());
+                let to_chunk = |position: Vec3| (block_origin + position * scale as f32).to_array();
 
-    samples[voxel_index as usize].0
-}
+                for quad in model.quads() {
+                    if quad.on_boundary {
+                        let neighbour = (IVec3::new(x as i32, y as i32, z as i32) + quad.normal.as_ivec3()).as_uvec3();
+                        if mesh_voxels[shape.linearize(neighbour.to_array()) as usize].get_visibility() == VoxelVisibility::Opaque {
+                            continue;
+                        }
+                    }
 
-fn voxel_texture_name(normal: Vec3, voxel_type: VoxelType) -> &'static str {
-    match voxel_type
-    {
-        VoxelType::Grass => {
-            if normal.y == 1.0 {
-                "grass_block_top.png"
-            } else if normal.y == -1.0 {
-                "dirt.png"
-            } else {
-                "grass_block_side.png"
-            }
-        }
-        VoxelType::Dirt => {
-            "dirt.png"
-        }
-        VoxelType::Stone => {
-            "stone.png"
-        }
-        VoxelType::Empty => {
-            "debug.png"
-        }
-        VoxelType::Sand => {
-            "sand.png"
-        }
-        VoxelType::OakLog => {
-            if normal.y == 1.0 || normal.y == -1.0 {
-                "oak_log_top.png"
-            } else {
-                "oak_log.png"
+                    let frame_name = block.texture(quad.normal);
+                    let positions = quad.positions.map(to_chunk);
+                    layers[MeshLayer::of(block) as usize].push_quad(
+                        positions,
+                        QUAD_INDICES,
+                        quad.normal,
+                        atlas.sub_uv(frame_name, quad.tex_coords),
+                        vertex_colors(block.tint(quad.normal), positions, model_brightness),
+                    );
+                }
             }
         }
-        VoxelType::OakLeaves => {
-            "oak_leaves.png"
+    }
+
+    let mut layers = layers.into_iter();
+    ChunkMeshes {
+        layers: MESH_LAYERS.map(|layer| layers.next().unwrap().build(layer.origin())),
+    }
+}
+
+fn face_to_block<S: Shape<3, Coord = u32>>(samples: &[MaterialVoxel], shape: &S, quad: &UnitQuad) -> BlockId {
+    samples[shape.linearize(quad.minimum) as usize].0
+}
+
+/// Render entities of a chunk's sub-meshes, children of the chunk entity, indexed like [`MESH_LAYERS`].
+#[derive(Component)]
+struct ChunkMeshEntities([Entity; MESH_LAYERS.len()]);
+
+#[derive(Component)]
+struct ChunkMesh;
+
+/// Bevy only computes an [`Aabb`] for meshes without one, so it is set here on every remesh,
+/// otherwise frustum culling would use the bounds of the first mesh.
+fn set_chunk_mesh(entity: &mut EntityCommands, meshes: &mut Assets<Mesh>, mesh: Option<Mesh>) {
+    match mesh.and_then(|mesh| mesh.compute_aabb().map(|aabb| (mesh, aabb))) {
+        Some((mesh, aabb)) => {
+            entity.insert((meshes.add(mesh), aabb));
         }
-        VoxelType::Cobblestone => {
-            "cobblestone.png"
+        None => {
+            entity.remove::<(Handle<Mesh>, Aabb)>();
         }
     }
 }
 
-fn atlas_uv(atlas: &TextureAtlas, desc: &Frame) -> Vec<[f32; 2]> {
-    vec![
-        [desc.x / atlas.meta.size.w, (desc.y + desc.h) / atlas.meta.size.h],
-        [(desc.x + desc.w) / atlas.meta.size.w, (desc.y + desc.h) / atlas.meta.size.h],
-        [desc.x / atlas.meta.size.w, desc.y / atlas.meta.size.h],
-        [(desc.x + desc.w) / atlas.meta.size.w, desc.y / atlas.meta.size.h]]
+/// Chunk sub-meshes drawn by the camera in the last frame, to check that frustum culling works.
+#[derive(Resource, Default)]
+pub struct ChunkCullingStats {
+    pub visible: usize,
+    pub total: usize,
 }
 
+fn update_culling_stats(
+    cameras: Query<&VisibleEntities, With<Camera3d>>,
+    chunk_meshes: Query<Entity, (With<ChunkMesh>, With<Handle<Mesh>>)>,
+    mut stats: ResMut<ChunkCullingStats>,
+) {
+    if let Ok(visible_entities) = cameras.get_single() {
+        stats.visible = visible_entities.iter().filter(|entity| chunk_meshes.contains(**entity)).count();
+        stats.total = chunk_meshes.iter().count();
+    }
+}
 
 fn spawn_pbr(
     commands: &mut Commands,
     meshes: &mut Assets<Mesh>,
-    mesh: Mesh,
-    material_handle: Handle<StandardMaterial>,
+    chunk_meshes: ChunkMeshes,
+    materials: &ChunkMaterials,
     transform: Transform,
     samples: Vec<MaterialVoxel>,
-) {
-    let handle = meshes.add(mesh.clone());
-    commands.spawn(PbrBundle {
-        mesh: handle,
-        material: material_handle,
-        transform,
-        ..Default::default()
-    })
-        .insert(RigidBody::Fixed)
-        .insert(Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh).unwrap())
-        // TODO: why chunks doesn't render without NoFrustumCulling ?
-        // this also fixes bad shadows
-        .insert(NoFrustumCulling)
-        .insert(ChunkInfo { samples });
-}
+    collider: Option<Collider>,
+) -> Entity {
+    let mut mesh_entities = Vec::with_capacity(MESH_LAYERS.len());
+    for (layer, mesh) in MESH_LAYERS.into_iter().zip(chunk_meshes.layers) {
+        let mut mesh_entity = commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(layer.origin())),
+            ChunkMesh,
+        ));
+        materials.insert(layer, &mut mesh_entity);
+        if layer.is_blended() {
+            mesh_entity.insert(NotShadowCaster);
+        }
+        set_chunk_mesh(&mut mesh_entity, meshes, mesh);
+        mesh_entities.push(mesh_entity.id());
+    }
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
-enum VoxelType {
-    Empty,
-    Grass,
-    Stone,
-    Cobblestone,
-    Dirt,
-    Sand,
-    OakLog,
-    OakLeaves
+    let mut entity = commands.spawn((
+        SpatialBundle::from_transform(transform),
+        RigidBody::Fixed,
+        ChunkLod::default(),
+        ChunkInfo { samples },
+        ChunkMeshEntities(mesh_entities.clone().try_into().unwrap()),
+    ));
+    entity.push_children(&mesh_entities);
+    if let Some(collider) = collider {
+        entity.insert(collider);
+    }
+    entity.id()
 }
 
 #[derive(Clone, Copy, Eq, PartialEq)]
-struct MaterialVoxel(VoxelType);
+struct MaterialVoxel(BlockId);
 
-const EMPTY: MaterialVoxel = MaterialVoxel(VoxelType::Empty);
+const EMPTY: MaterialVoxel = MaterialVoxel(BlockId::AIR);
 
-impl Voxel for MaterialVoxel {
-    fn get_visibility(&self) -> VoxelVisibility {
-        match self.0 {
-            VoxelType::Empty => VoxelVisibility::Empty,
-            VoxelType::OakLeaves => VoxelVisibility::Always,
-            _ => VoxelVisibility::Opaque
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+    use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+    use bevy::render::view::VisibilityPlugin;
+
+    use super::*;
+
+    /// Stone column of `height` voxels in the corner of an otherwise empty chunk.
+    fn column_samples(stone: BlockId, height: u32) -> Vec<MaterialVoxel> {
+        let mut samples = vec![EMPTY; SampleShape::SIZE as usize];
+        for y in 1..=height {
+            samples[SampleShape::linearize([1, y, 1]) as usize] = MaterialVoxel(stone);
         }
+        samples
+    }
+
+    /// Corners of the opaque sub-mesh bounds of a chunk.
+    fn opaque_bounds(app: &App, chunk: Entity) -> Option<(Vec3, Vec3)> {
+        let mesh_entity = app.world.get::<ChunkMeshEntities>(chunk).unwrap().0[MeshLayer::Opaque as usize];
+        app.world.get::<Aabb>(mesh_entity).map(|aabb| (aabb.min().into(), aabb.max().into()))
+    }
+
+    #[test]
+    fn remeshed_chunks_get_new_bounds_and_are_culled_behind_the_camera() {
+        let list: BlockList = serde_json::from_str(include_str!("../assets/default.blocks.json")).unwrap();
+        let registry = BlockRegistry::new(list);
+        let stone = registry.id("stone");
+        let white = Image::new_fill(
+            Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            &[255; 4],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        let tint = BiomeTint::new(&white, &white).unwrap();
+        let atlas = BlockAtlas::fallback_only();
+        let light = LightMap::default();
+
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, AssetPlugin::default(), TransformPlugin, VisibilityPlugin))
+            .add_asset::<Mesh>()
+            .init_resource::<ChunkMap>()
+            .init_resource::<ChunkRemeshSettings>()
+            .init_resource::<ChunkCullingStats>()
+            .add_systems(Update, remesh_dirty_chunks)
+            .add_systems(PostUpdate, update_culling_stats.after(VisibilitySystems::CheckVisibility));
+
+        // the camera looks down -Z, the second chunk lies behind it
+        app.world.spawn(Camera3dBundle::default());
+        let materials = ChunkMaterials {
+            opaque: Handle::default(),
+            cutout: Handle::default(),
+            translucent: Handle::default(),
+            emissive: Handle::default(),
+            water: Handle::default(),
+        };
+        let mut queue = CommandQueue::default();
+        let chunks = app.world.resource_scope(|world, mut meshes: Mut<Assets<Mesh>>| {
+            let mut commands = Commands::new(&mut queue, world);
+            let context = MeshContext { registry: &registry, atlas: &atlas, tint: &tint, light: &light };
+            [-2, 1].map(|z| {
+                let origin = Vec3::new(0.0, 0.0, (z * CHUNK_SIZE) as f32);
+                let samples = column_samples(stone, 4);
+                let chunk_meshes = generate_simple_mesh(&samples, origin, &context);
+                let collider = generate_collider(&samples, &registry);
+                spawn_pbr(&mut commands, &mut meshes, chunk_meshes, &materials, Transform::from_translation(origin), samples, collider)
+            })
+        });
+        queue.apply(&mut app.world);
+        app
+            .insert_resource(registry)
+            .insert_resource(AtlasLoading {
+                loaded: true,
+                textures: BlockTextureLoading::default(),
+                blocks: Handle::default(),
+                colormaps: Default::default(),
+                atlas: Some(atlas),
+            })
+            .insert_resource(tint)
+            .insert_resource(light);
+
+        app.update();
+        assert_eq!(opaque_bounds(&app, chunks[0]), Some((Vec3::ONE, Vec3::new(2.0, 5.0, 2.0))));
+
+        // grow the column of both chunks, their meshes and bounds must follow
+        for chunk in chunks {
+            app.world.get_mut::<ChunkInfo>(chunk).unwrap().samples = column_samples(stone, 10);
+            app.world.entity_mut(chunk).insert(DirtyChunk);
+        }
+        app.update();
+
+        let grown = Some((Vec3::ONE, Vec3::new(2.0, 11.0, 2.0)));
+        assert_eq!(opaque_bounds(&app, chunks[0]), grown);
+        assert_eq!(opaque_bounds(&app, chunks[1]), grown);
+        assert!(app.world.get::<DirtyChunk>(chunks[0]).is_none());
+
+        let stats = app.world.resource::<ChunkCullingStats>();
+        assert_eq!(stats.total, 2);
+        assert_eq!(stats.visible, 1);
     }
 }
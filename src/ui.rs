@@ -1,6 +1,15 @@
 use bevy::prelude::*;
 
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, DiagnosticsStore, Diagnostic, RegisterDiagnostic};
+use bevy::input::mouse::MouseWheel;
+
+use bevy_fps_controller::controller::LogicalPlayer;
+
+use crate::atlas::BlockIcons;
+use crate::console::ConsoleState;
+use crate::inventory::{Inventory, InventoryScreen, HOTBAR_SLOTS};
+use crate::items::{Item, ItemStack};
+use crate::terrain::ChunkCullingStats;
 
 pub struct MyUiPlugin;
 
@@ -8,8 +17,8 @@ impl Plugin for MyUiPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_plugins(FrameTimeDiagnosticsPlugin::default())
-            .add_systems(Startup, setup)
-            .add_systems(Update, text_update_system)
+            .add_systems(Startup, (setup, setup_hotbar))
+            .add_systems(Update, (text_update_system, (select_hotbar_slot, update_hotbar, update_item_views).chain()))
             .register_diagnostic(Diagnostic::new(FrameTimeDiagnosticsPlugin::FPS, "FPS", 10));
     }
 }
@@ -70,12 +79,255 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         });
 }
 
-fn text_update_system(diagnostics: Res<DiagnosticsStore>, mut query: Query<&mut Text, With<FpsText>>) {
+fn text_update_system(
+    diagnostics: Res<DiagnosticsStore>,
+    culling: Res<ChunkCullingStats>,
+    mut query: Query<&mut Text, With<FpsText>>,
+) {
     for mut text in &mut query {
         if let Some(fps) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS) {
             if let Some(value) = fps.smoothed() {
-                text.sections[0].value = format!("FPS {:.0}", value.round());
+                text.sections[0].value = format!("FPS {:.0}\nChunks {}/{}", value.round(), culling.visible, culling.total);
+            }
+        }
+    }
+}
+
+/// A slot node showing an item stack, its parts are spawned by [`spawn_item_view_parts`].
+#[derive(Component, Default, PartialEq)]
+pub struct ItemView(pub Option<ItemStack>);
+
+#[derive(Component)]
+struct BlockIcon;
+
+#[derive(Component)]
+struct ToolIcon;
+
+#[derive(Component)]
+struct StackCount;
+
+/// Durability left of the tool in the slot.
+#[derive(Component)]
+struct DurabilityBar;
+
+/// Spawns the icons, count and durability bar of an [`ItemView`] in its 48 pixel node.
+pub fn spawn_item_view_parts(parent: &mut ChildBuilder, font: Handle<Font>) {
+    parent.spawn((
+        AtlasImageBundle {
+            style: Style {
+                width: Val::Px(32.0),
+                height: Val::Px(32.0),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        BlockIcon,
+    ));
+    parent.spawn((
+        ImageBundle {
+            style: Style {
+                // on top of the block icon, only one of them is shown
+                position_type: PositionType::Absolute,
+                width: Val::Px(32.0),
+                height: Val::Px(32.0),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        ToolIcon,
+    ));
+    parent.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font,
+                font_size: 16.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(2.0),
+            bottom: Val::Px(0.0),
+            ..default()
+        }),
+        StackCount,
+    ));
+    parent.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(4.0),
+                bottom: Val::Px(2.0),
+                height: Val::Px(3.0),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        DurabilityBar,
+    ));
+}
+
+#[allow(clippy::type_complexity)]
+fn update_item_views(
+    icons: Option<Res<BlockIcons>>,
+    asset_server: Res<AssetServer>,
+    views: Query<Ref<ItemView>>,
+    mut block_icons: Query<
+        (&Parent, &mut Handle<TextureAtlas>, &mut UiTextureAtlasImage, &mut Visibility),
+        With<BlockIcon>,
+    >,
+    mut tool_icons: Query<(&Parent, &mut UiImage, &mut Visibility), (With<ToolIcon>, Without<BlockIcon>)>,
+    mut counts: Query<(&Parent, &mut Text), With<StackCount>>,
+    mut bars: Query<
+        (&Parent, &mut Style, &mut BackgroundColor, &mut Visibility),
+        (With<DurabilityBar>, Without<BlockIcon>, Without<ToolIcon>),
+    >,
+) {
+    let Some(icons) = icons else {
+        return;
+    };
+    // the view of a part if it needs redrawing
+    let changed_view = |parent: &Parent| {
+        views.get(parent.get()).ok()
+            .filter(|view| view.is_changed() || icons.is_added())
+            .map(|view| view.0)
+    };
+
+    for (parent, mut atlas, mut image, mut visibility) in &mut block_icons {
+        let Some(stack) = changed_view(parent) else {
+            continue;
+        };
+        match stack.map(|stack| stack.item) {
+            Some(Item::Block(block)) => {
+                *atlas = icons.0.clone();
+                image.index = block.0 as usize;
+                *visibility = Visibility::Inherited;
+            }
+            _ => *visibility = Visibility::Hidden,
+        }
+    }
+    for (parent, mut image, mut visibility) in &mut tool_icons {
+        let Some(stack) = changed_view(parent) else {
+            continue;
+        };
+        match stack.and_then(|stack| stack.tool()) {
+            Some(tool) => {
+                image.texture = asset_server.load(tool.texture());
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+    for (parent, mut text) in &mut counts {
+        let Some(stack) = changed_view(parent) else {
+            continue;
+        };
+        text.sections[0].value = match stack {
+            Some(stack) if stack.count > 1 => stack.count.to_string(),
+            _ => String::new(),
+        };
+    }
+    for (parent, mut style, mut color, mut visibility) in &mut bars {
+        let Some(stack) = changed_view(parent) else {
+            continue;
+        };
+        match stack.and_then(|stack| stack.wear()) {
+            // new tools don't show their bar
+            Some(wear) if wear < 1.0 => {
+                style.width = Val::Px(34.0 * wear);
+                *color = Color::rgb(1.0 - wear, wear, 0.0).into();
+                *visibility = Visibility::Inherited;
+            }
+            _ => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+/// A slot of the hotbar, showing the first inventory row.
+#[derive(Component)]
+struct HotbarSlot(usize);
+
+fn setup_hotbar(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for slot in 0..HOTBAR_SLOTS {
+                parent
+                    .spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Px(48.0),
+                                height: Val::Px(48.0),
+                                margin: UiRect::all(Val::Px(2.0)),
+                                border: UiRect::all(Val::Px(3.0)),
+                                align_items: AlignItems::Center,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+                            ..default()
+                        },
+                        HotbarSlot(slot),
+                        ItemView::default(),
+                    ))
+                    .with_children(|parent| spawn_item_view_parts(parent, font.clone()));
             }
+        });
+}
+
+fn select_hotbar_slot(
+    key: Res<Input<KeyCode>>,
+    mut wheel: EventReader<MouseWheel>,
+    console: Res<ConsoleState>,
+    screen: Res<InventoryScreen>,
+    mut players: Query<&mut Inventory, With<LogicalPlayer>>,
+) {
+    let Ok(mut inventory) = players.get_single_mut() else {
+        return;
+    };
+    if console.is_open() || screen.is_open() {
+        wheel.clear();
+        return;
+    }
+    let keys = [
+        KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5,
+        KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    ];
+    for (slot, key_code) in keys.into_iter().enumerate() {
+        if key.just_pressed(key_code) {
+            inventory.selected = slot;
         }
     }
+    for event in wheel.iter() {
+        // scrolling down moves to the right like in Minecraft
+        let step = if event.y < 0.0 { 1 } else if event.y > 0.0 { HOTBAR_SLOTS - 1 } else { 0 };
+        inventory.selected = (inventory.selected + step) % HOTBAR_SLOTS;
+    }
+}
+
+fn update_hotbar(
+    players: Query<&Inventory, (With<LogicalPlayer>, Changed<Inventory>)>,
+    mut slots: Query<(&HotbarSlot, &mut ItemView, &mut BorderColor)>,
+) {
+    let Ok(inventory) = players.get_single() else {
+        return;
+    };
+    for (slot, mut view, mut border) in &mut slots {
+        view.set_if_neq(ItemView(inventory.slots[slot.0]));
+        *border = BorderColor(if slot.0 == inventory.selected { Color::WHITE } else { Color::rgba(0.3, 0.3, 0.3, 0.8) });
+    }
 }
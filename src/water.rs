@@ -0,0 +1,72 @@
+//! Water surface material for the water sub-mesh of chunks: animated wave normals, fresnel
+//! transparency, tinting by the depth of the water below and reflections of the sky cubemap.
+
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+use crate::skybox::Cubemap;
+use crate::weather::Weather;
+use crate::world_time::WorldTime;
+
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(MaterialPlugin::<WaterMaterial>::default())
+            .add_systems(Update, update_water);
+    }
+}
+
+#[derive(AsBindGroup, TypeUuid, TypePath, Clone)]
+#[uuid = "7d3b9a16-25e8-4c0f-b4a7-e85c1f6d2b93"]
+pub struct WaterMaterial {
+    /// Color of shallow water, its alpha is how much of the ground shows through.
+    #[uniform(0)]
+    shallow_color: Color,
+    #[uniform(0)]
+    deep_color: Color,
+    /// Reflections of the day sky are dimmed at night and under clouds.
+    #[uniform(0)]
+    sky_brightness: f32,
+    #[texture(1, dimension = "cube")]
+    #[sampler(2)]
+    sky: Option<Handle<Image>>,
+}
+
+impl Default for WaterMaterial {
+    fn default() -> Self {
+        Self {
+            shallow_color: Color::rgba(0.15, 0.45, 0.55, 0.35),
+            deep_color: Color::rgba(0.02, 0.1, 0.2, 0.9),
+            sky_brightness: 1.0,
+            sky: None,
+        }
+    }
+}
+
+impl Material for WaterMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/water.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+}
+
+fn update_water(
+    time: Res<WorldTime>,
+    weather: Res<Weather>,
+    cubemap: Res<Cubemap>,
+    mut materials: ResMut<Assets<WaterMaterial>>,
+) {
+    let sky_brightness = (0.05 + 0.95 * time.daylight()) * weather.sunlight();
+    for (_, material) in materials.iter_mut() {
+        material.sky = cubemap.day().cloned();
+        material.sky_brightness = sky_brightness;
+    }
+}
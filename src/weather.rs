@@ -0,0 +1,342 @@
+//! Weather: clear skies, rain and thunderstorms that change over time. Precipitation is a
+//! mesh of particles animated in the vertex shader around the camera, rain turns to snow in
+//! cold biomes and stops under blocks through a heightmap of the columns around the camera.
+
+use std::collections::HashMap;
+
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::{
+        mesh::Indices,
+        render_resource::{AsBindGroup, Extent3d, PrimitiveTopology, ShaderRef, TextureDimension, TextureFormat},
+        view::NoFrustumCulling,
+    },
+};
+use rand::Rng;
+
+use crate::biomes::BiomeTint;
+use crate::console::{ConsoleCommand, ConsoleReply};
+use crate::terrain::{ChunkInfo, VoxelWorld, CHUNK_SIZE};
+use crate::world_time::WorldTime;
+use crate::DigEvent;
+
+/// Has to match `PARTICLE_COUNT` in `precipitation.wgsl`.
+const PARTICLE_COUNT: usize = 6000;
+/// Columns around the camera covered by the heightmap, in each direction.
+const HEIGHTMAP_RADIUS: i32 = 24;
+/// Seconds precipitation takes to start or stop.
+const TRANSITION_SECONDS: f32 = 20.0;
+/// Columns colder than this get snow instead of rain.
+const SNOW_TEMPERATURE: f32 = 0.3;
+/// Height of a column nothing stops precipitation in.
+const OPEN_SKY: f32 = -10000.0;
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(MaterialPlugin::<PrecipitationMaterial>::default())
+            .init_resource::<Weather>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, (weather_command, change_weather, update_precipitation).chain())
+            // after the dig events changed the chunks
+            .add_systems(PostUpdate, update_heightmap);
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+    Thunderstorm,
+}
+
+#[derive(Resource)]
+pub struct Weather {
+    pub kind: WeatherKind,
+    /// Strength of the precipitation from 0 to 1, it follows `kind` over [`TRANSITION_SECONDS`].
+    intensity: f32,
+    /// Darkness of the clouds from 0 to 1, storms are darker than rain.
+    storm: f32,
+    /// Seconds until the weather changes on its own.
+    remaining: f32,
+    /// Brightness of the current lightning flash.
+    flash: f32,
+    snow: bool,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self { kind: WeatherKind::Clear, intensity: 0.0, storm: 0.0, remaining: 600.0, flash: 0.0, snow: false }
+    }
+}
+
+impl Weather {
+    /// Factor for sunlight and moonlight, clouds block part of it.
+    pub fn sunlight(&self) -> f32 {
+        1.0 - 0.5 * self.intensity - 0.3 * self.storm
+    }
+
+    /// How much clouds cover the sky, from 0 to 1.
+    pub fn overcast(&self) -> f32 {
+        (0.7 * self.intensity + 0.3 * self.storm).min(1.0)
+    }
+
+    /// Ambient light added by lightning.
+    pub fn flash(&self) -> f32 {
+        self.flash
+    }
+
+    fn target(&self) -> (f32, f32) {
+        match self.kind {
+            WeatherKind::Clear => (0.0, 0.0),
+            WeatherKind::Rain => (1.0, 0.0),
+            WeatherKind::Thunderstorm => (1.0, 1.0),
+        }
+    }
+}
+
+#[derive(AsBindGroup, TypeUuid, TypePath, Clone)]
+#[uuid = "b4e6f0d2-9c3a-4f57-8e1b-6a2d7c5f3e90"]
+pub struct PrecipitationMaterial {
+    #[uniform(0)]
+    time: f32,
+    #[uniform(0)]
+    intensity: f32,
+    #[uniform(0)]
+    snow: f32,
+    #[uniform(0)]
+    brightness: f32,
+    /// World x and z of the first heightmap texel.
+    #[uniform(0)]
+    heightmap_origin: Vec2,
+    #[texture(1, sample_type = "float", filterable = false)]
+    heightmap: Handle<Image>,
+}
+
+impl Material for PrecipitationMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/precipitation.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/precipitation.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+}
+
+/// Column heights around the camera, uploaded to the precipitation heightmap.
+#[derive(Resource)]
+struct Heightmap {
+    image: Handle<Image>,
+    material: Handle<PrecipitationMaterial>,
+    /// Column of the first texel, `None` until the heightmap is filled.
+    origin: Option<IVec2>,
+    /// Heights of the columns in the heightmap, only loaded columns are cached.
+    columns: HashMap<[i32; 2], f32>,
+    /// Whether every column of the heightmap was loaded when it was last filled.
+    complete: bool,
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<PrecipitationMaterial>>,
+) {
+    let size = (HEIGHTMAP_RADIUS * 2) as u32;
+    let image = images.add(Image::new_fill(
+        Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &OPEN_SKY.to_ne_bytes(),
+        TextureFormat::R32Float,
+    ));
+    let material = materials.add(PrecipitationMaterial {
+        time: 0.0,
+        intensity: 0.0,
+        snow: 0.0,
+        brightness: 1.0,
+        heightmap_origin: Vec2::ZERO,
+        heightmap: image.clone(),
+    });
+
+    // every particle is a quad, all four corners get the particle's random position in the
+    // unit cube and the shader moves it into the area around the camera
+    let mut rng = rand::thread_rng();
+    let mut positions = Vec::with_capacity(PARTICLE_COUNT * 4);
+    let mut uvs = Vec::with_capacity(PARTICLE_COUNT * 4);
+    let mut indices = Vec::with_capacity(PARTICLE_COUNT * 6);
+    for particle in 0..PARTICLE_COUNT as u32 {
+        let seed: [f32; 3] = [rng.gen(), rng.gen(), rng.gen()];
+        positions.extend([seed; 4]);
+        uvs.extend([[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        indices.extend([0, 1, 2, 0, 2, 3].map(|index| particle * 4 + index));
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+
+    commands.spawn((
+        MaterialMeshBundle {
+            mesh: meshes.add(mesh),
+            material: material.clone(),
+            ..default()
+        },
+        NoFrustumCulling,
+        NotShadowCaster,
+        NotShadowReceiver,
+    ));
+
+    commands.insert_resource(Heightmap { image, material, origin: None, columns: HashMap::new(), complete: false });
+}
+
+fn change_weather(
+    time: Res<Time>,
+    tint: Option<Res<BiomeTint>>,
+    camera: Query<&GlobalTransform, With<Camera3d>>,
+    mut weather: ResMut<Weather>,
+) {
+    let delta = time.delta_seconds();
+    let mut rng = rand::thread_rng();
+
+    weather.remaining -= delta;
+    if weather.remaining <= 0.0 {
+        weather.kind = match rng.gen_range(0..10) {
+            0..=5 => WeatherKind::Clear,
+            6..=8 => WeatherKind::Rain,
+            _ => WeatherKind::Thunderstorm,
+        };
+        weather.remaining = rng.gen_range(300.0..900.0);
+    }
+
+    let (intensity, storm) = weather.target();
+    let step = delta / TRANSITION_SECONDS;
+    weather.intensity += (intensity - weather.intensity).clamp(-step, step);
+    weather.storm += (storm - weather.storm).clamp(-step, step);
+
+    weather.flash = (weather.flash - delta * 4.0).max(0.0);
+    if weather.storm > 0.5 && rng.gen_bool((delta as f64 * 0.08).min(1.0)) {
+        weather.flash = 1.0;
+    }
+
+    if let (Some(tint), Ok(camera)) = (tint, camera.get_single()) {
+        let position = camera.translation();
+        weather.snow = tint.temperature(position.x, position.z) < SNOW_TEMPERATURE;
+    }
+}
+
+fn update_precipitation(
+    time: Res<Time>,
+    world_time: Res<WorldTime>,
+    weather: Res<Weather>,
+    heightmap: Res<Heightmap>,
+    mut materials: ResMut<Assets<PrecipitationMaterial>>,
+) {
+    let Some(material) = materials.get_mut(&heightmap.material) else {
+        return;
+    };
+    material.time = time.elapsed_seconds_wrapped();
+    material.intensity = weather.intensity;
+    material.snow = if weather.snow { 1.0 } else { 0.0 };
+    material.brightness = (0.1 + 0.9 * world_time.daylight()) * weather.sunlight() + weather.flash;
+    if let Some(origin) = heightmap.origin {
+        material.heightmap_origin = origin.as_vec2();
+    }
+}
+
+fn update_heightmap(
+    voxel_world: VoxelWorld,
+    camera: Query<&GlobalTransform, With<Camera3d>>,
+    new_chunks: Query<&Transform, Added<ChunkInfo>>,
+    mut dig_events: EventReader<DigEvent>,
+    mut heightmap: ResMut<Heightmap>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let mut changed = false;
+    for ev in dig_events.iter() {
+        let voxel = ev.world_position.floor().as_ivec3();
+        changed |= heightmap.columns.remove(&[voxel.x, voxel.z]).is_some();
+    }
+    // a new chunk can change the columns above or below it, remeshes don't change any
+    for transform in &new_chunks {
+        // interior samples start at 1
+        let min = transform.translation.xz().as_ivec2() + IVec2::ONE;
+        let max = min + IVec2::splat(CHUNK_SIZE);
+        heightmap.columns.retain(|[x, z], _| !((min.x..max.x).contains(x) && (min.y..max.y).contains(z)));
+        changed = true;
+    }
+
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+    let column = camera.translation().xz().floor().as_ivec2();
+    let origin = column - IVec2::splat(HEIGHTMAP_RADIUS);
+    if !changed && heightmap.complete && heightmap.origin == Some(origin) {
+        return;
+    }
+
+    let Some(image) = images.get_mut(&heightmap.image) else {
+        return;
+    };
+    let size = HEIGHTMAP_RADIUS * 2;
+    let mut data = Vec::with_capacity(image.data.len());
+    let mut complete = true;
+    for z in origin.y..origin.y + size {
+        for x in origin.x..origin.x + size {
+            let height = match heightmap.columns.get(&[x, z]) {
+                Some(height) => *height,
+                // columns outside of the loaded chunks are retried until they load
+                None => match voxel_world.precipitation_height(x, z) {
+                    Some(height) => *heightmap.columns.entry([x, z]).or_insert(height as f32),
+                    None => {
+                        complete = false;
+                        OPEN_SKY
+                    }
+                },
+            };
+            data.extend(height.to_ne_bytes());
+        }
+    }
+    image.data = data;
+    heightmap.columns.retain(|[x, z], _| {
+        (origin.x..origin.x + size).contains(x) && (origin.y..origin.y + size).contains(z)
+    });
+    heightmap.origin = Some(origin);
+    heightmap.complete = complete;
+}
+
+fn weather_command(
+    mut commands: EventReader<ConsoleCommand>,
+    mut replies: EventWriter<ConsoleReply>,
+    mut weather: ResMut<Weather>,
+) {
+    for command in commands.iter().filter(|command| command.name == "weather") {
+        let kind = match command.args.first().map(String::as_str) {
+            Some("clear") => WeatherKind::Clear,
+            Some("rain") => WeatherKind::Rain,
+            Some("thunder") => WeatherKind::Thunderstorm,
+            _ => {
+                replies.send(ConsoleReply("Usage: /weather <clear|rain|thunder> [seconds]".to_string()));
+                continue;
+            }
+        };
+        let remaining = match command.args.get(1).map(|seconds| seconds.parse::<f32>()) {
+            None => 600.0,
+            Some(Ok(seconds)) if seconds.is_finite() && seconds > 0.0 => seconds,
+            Some(_) => {
+                replies.send(ConsoleReply("Usage: /weather <clear|rain|thunder> [seconds]".to_string()));
+                continue;
+            }
+        };
+        weather.kind = kind;
+        weather.remaining = remaining;
+        replies.send(ConsoleReply(format!("Weather set to {kind:?}")));
+    }
+}
@@ -0,0 +1,218 @@
+//! Time of day: it moves the sun and the moon, scales their light and the ambient light,
+//! and is saved with the world in `saves/world.json`.
+
+use std::f32::consts::TAU;
+use std::fs;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::console::{ConsoleCommand, ConsoleReply};
+use crate::weather::Weather;
+
+const SAVE_PATH: &str = "saves/world.json";
+
+const SUN_ILLUMINANCE: f32 = 12000.0;
+const MOON_ILLUMINANCE: f32 = 400.0;
+
+pub struct WorldTimePlugin;
+
+impl Plugin for WorldTimePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(load_world_time())
+            .add_systems(Startup, setup)
+            .add_systems(Update, (advance_time, time_command, update_lights).chain())
+            .add_systems(Last, save_on_exit);
+    }
+}
+
+/// Hours since midnight, the sun rises at 6 and sets at 18.
+#[derive(Resource, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WorldTime {
+    pub hours: f32,
+    /// Real seconds a whole day lasts.
+    #[serde(default = "default_day_length")]
+    pub day_length: f32,
+}
+
+fn default_day_length() -> f32 {
+    1200.0
+}
+
+impl Default for WorldTime {
+    fn default() -> Self {
+        Self { hours: 8.0, day_length: default_day_length() }
+    }
+}
+
+impl WorldTime {
+    /// Unit vector towards the sun, it rises in the east (+X) and passes a little south (+Z).
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.hours - 6.0) / 24.0 * TAU;
+        Vec3::new(angle.cos(), angle.sin(), 0.25).normalize()
+    }
+
+    pub fn moon_direction(&self) -> Vec3 {
+        let sun = self.sun_direction();
+        Vec3::new(-sun.x, -sun.y, sun.z)
+    }
+
+    /// 1 during the day and 0 at night, changing while the sun is close to the horizon.
+    pub fn daylight(&self) -> f32 {
+        smoothstep(-0.1, 0.15, self.sun_direction().y)
+    }
+
+    /// 1 with the sun at the horizon, for the warm colors of dawn and dusk.
+    pub fn twilight(&self) -> f32 {
+        (1.0 - self.sun_direction().y.abs() / 0.25).clamp(0.0, 1.0)
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// World data kept between sessions.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WorldSave {
+    time: WorldTime,
+}
+
+fn load_world_time() -> WorldTime {
+    let Ok(json) = fs::read_to_string(SAVE_PATH) else {
+        return WorldTime::default();
+    };
+    match serde_json::from_str::<WorldSave>(&json) {
+        Ok(save) => save.time,
+        Err(err) => {
+            warn!("Can't read {SAVE_PATH}: {err}");
+            WorldTime::default()
+        }
+    }
+}
+
+fn save_world_time(time: &WorldTime) {
+    let save = WorldSave { time: time.clone() };
+    let result = fs::create_dir_all("saves")
+        .map_err(|err| err.to_string())
+        .and_then(|_| serde_json::to_string_pretty(&save).map_err(|err| err.to_string()))
+        .and_then(|json| fs::write(SAVE_PATH, json).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        warn!("Can't save {SAVE_PATH}: {err}");
+    }
+}
+
+#[derive(Component)]
+struct Sun;
+
+#[derive(Component)]
+struct Moon;
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                illuminance: SUN_ILLUMINANCE,
+                shadows_enabled: true,
+                ..default()
+            },
+            // TODO: why with cascade_shadow_config, shadows disappear?
+            // cascade_shadow_config: bevy::pbr::CascadeShadowConfigBuilder {
+            //     maximum_distance: 3.0,
+            //     first_cascade_far_bound: 0.9,
+            //     ..default()
+            // }.into(),
+            ..default()
+        },
+        Sun,
+    ));
+
+    commands.spawn((
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                illuminance: 0.0,
+                color: Color::rgb(0.6, 0.7, 1.0),
+                shadows_enabled: false,
+                ..default()
+            },
+            ..default()
+        },
+        Moon,
+    ));
+}
+
+fn advance_time(real_time: Res<Time>, mut time: ResMut<WorldTime>) {
+    let day_length = time.day_length;
+    time.hours = (time.hours + real_time.delta_seconds() / day_length * 24.0) % 24.0;
+}
+
+fn time_command(
+    mut commands: EventReader<ConsoleCommand>,
+    mut replies: EventWriter<ConsoleReply>,
+    mut time: ResMut<WorldTime>,
+) {
+    for command in commands.iter().filter(|command| command.name == "time") {
+        let args: Vec<&str> = command.args.iter().map(String::as_str).collect();
+        let hours = match args.as_slice() {
+            ["set", "day"] => Some(7.0),
+            ["set", "noon"] => Some(12.0),
+            ["set", "night"] => Some(19.0),
+            ["set", "midnight"] => Some(0.0),
+            ["set", value] => value.parse::<f32>().ok().filter(|hours| (0.0..24.0).contains(hours)),
+            ["query"] | [] => {
+                replies.send(ConsoleReply(format!("Time is {:.1}", time.hours)));
+                continue;
+            }
+            _ => None,
+        };
+        match hours {
+            Some(hours) => {
+                time.hours = hours;
+                save_world_time(&time);
+                replies.send(ConsoleReply(format!("Time set to {hours:.1}")));
+            }
+            None => replies.send(ConsoleReply("Usage: /time set <0-24|day|noon|night|midnight>".to_string())),
+        }
+    }
+}
+
+fn update_lights(
+    time: Res<WorldTime>,
+    weather: Res<Weather>,
+    mut sun: Query<(&mut Transform, &mut DirectionalLight), (With<Sun>, Without<Moon>)>,
+    mut moon: Query<(&mut Transform, &mut DirectionalLight), (With<Moon>, Without<Sun>)>,
+    mut ambient: ResMut<AmbientLight>,
+) {
+    let daylight = time.daylight();
+    let twilight = time.twilight();
+    let sunlight = weather.sunlight();
+
+    for (mut transform, mut light) in &mut sun {
+        *transform = Transform::default().looking_to(-time.sun_direction(), Vec3::Y);
+        light.illuminance = SUN_ILLUMINANCE * daylight * sunlight;
+        light.shadows_enabled = daylight > 0.0;
+        light.color = mix_colors(Color::WHITE, Color::rgb(1.0, 0.6, 0.35), twilight);
+    }
+    for (mut transform, mut light) in &mut moon {
+        *transform = Transform::default().looking_to(-time.moon_direction(), Vec3::Y);
+        light.illuminance = MOON_ILLUMINANCE * (1.0 - daylight) * sunlight;
+    }
+
+    let day_ambient = mix_colors(Color::rgb_u8(210, 220, 240), Color::rgb_u8(240, 170, 130), twilight);
+    ambient.color = mix_colors(Color::rgb_u8(70, 80, 130), day_ambient, daylight);
+    // lightning lights up everything for a moment
+    ambient.brightness = (0.08 + 0.42 * daylight) * sunlight + weather.flash();
+}
+
+pub fn mix_colors(from: Color, to: Color, amount: f32) -> Color {
+    let mixed = Vec4::from(from.as_rgba_f32()).lerp(Vec4::from(to.as_rgba_f32()), amount);
+    Color::rgba(mixed.x, mixed.y, mixed.z, mixed.w)
+}
+
+fn save_on_exit(exit: EventReader<AppExit>, time: Res<WorldTime>) {
+    if !exit.is_empty() {
+        save_world_time(&time);
+    }
+}